@@ -0,0 +1,119 @@
+use std::sync::{Arc, Mutex};
+
+use futures::{future::join_all, StreamExt};
+use tokio::sync::Semaphore;
+
+use crate::{
+    app::{
+        core::{concurrency::ConcurrencyControl, downloader::Downloader, signals::handle_signals},
+        show::dashboard::Dashboard,
+    },
+    arguments::options::DownloadOptions,
+    common::{
+        errors::{Error, Result},
+        tasks::TaskType,
+    },
+};
+
+/// The state of a task of the batch
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskState {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl TaskState {
+    /// Whether the task will not run anymore
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TaskState::Done | TaskState::Failed(_) | TaskState::Cancelled)
+    }
+}
+
+/// A task of the batch, which is shared by the workers and the dashboard
+pub struct BatchTask {
+    pub name: String,
+    pub task_type: TaskType,
+    /// Pause, resume, stop the task and change its concurrency
+    pub control: ConcurrencyControl,
+    state: Mutex<TaskState>,
+}
+
+impl BatchTask {
+    fn new(options: &DownloadOptions) -> BatchTask {
+        let output = options.output();
+        let name = output
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| options.url().to_string());
+        BatchTask {
+            name,
+            task_type: options.task_type(),
+            control: ConcurrencyControl::from_options(options),
+            state: Mutex::new(TaskState::Pending),
+        }
+    }
+
+    pub fn state(&self) -> TaskState {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn set_state(&self, state: TaskState) {
+        *self.state.lock().unwrap() = state;
+    }
+}
+
+/// Run the tasks of all urls with a dashboard, at most `jobs` tasks at the same time
+///
+/// Tasks are started by the downloader on its runtime, and the dashboard takes the current thread.
+/// Return whether all tasks succeed.
+pub fn run_batch(downloader: &Downloader, tasks: Vec<DownloadOptions>, jobs: u64) -> Result<bool> {
+    let batch: Vec<Arc<BatchTask>> = tasks.iter().map(|options| Arc::new(BatchTask::new(options))).collect();
+
+    // The dashboard must take the terminal before tasks run
+    let mut dashboard = Dashboard::new(batch.clone())?;
+    handle_signals(batch.iter().map(|task| task.control.clone()).collect())?;
+
+    // Tasks wait for a job in order
+    let jobs = Arc::new(Semaphore::new(jobs.max(1) as usize));
+    let handles: Vec<_> = tasks
+        .into_iter()
+        .zip(batch.iter().cloned())
+        .map(|(options, task)| {
+            let (downloader, jobs) = (downloader.clone(), jobs.clone());
+            downloader.handle().clone().spawn(async move {
+                let _job = jobs.acquire_owned().await;
+                if task.control.is_stopped() {
+                    task.set_state(TaskState::Cancelled);
+                    return;
+                }
+
+                task.set_state(TaskState::Running);
+                let mut download = downloader.download_with_control(options, task.control.clone());
+                // The dashboard reads the progress from the control
+                while download.next().await.is_some() {}
+                let state = match download.result().await {
+                    Ok(_) => TaskState::Done,
+                    Err(Error::Cancelled) => TaskState::Cancelled,
+                    Err(err) => {
+                        tracing::error!("Task {} fails: {:?}", task.name, err);
+                        TaskState::Failed(err.to_string())
+                    }
+                };
+                task.set_state(state);
+            })
+        })
+        .collect();
+
+    let result = dashboard.run();
+    if result.is_err() {
+        batch.iter().for_each(|task| task.control.stop());
+    }
+    // Tasks exit after they are finished or stopped
+    downloader.handle().block_on(join_all(handles));
+    result?;
+
+    Ok(batch.iter().all(|task| task.state() == TaskState::Done))
+}
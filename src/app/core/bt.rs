@@ -1,15 +1,27 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use futures::{pin_mut, select, FutureExt};
 use librqbit::{
-    api::TorrentIdOrHash, dht::PersistentDhtConfig, AddTorrent, AddTorrentOptions, AddTorrentResponse, Api,
-    PeerConnectionOptions, Session, SessionOptions, SessionPersistenceConfig,
+    api::TorrentIdOrHash, dht::PersistentDhtConfig, limits::LimitsConfig, AddTorrent, AddTorrentOptions,
+    AddTorrentResponse, Api, ManagedTorrent, PeerConnectionOptions, Session, SessionOptions, SessionPersistenceConfig,
 };
+use tokio::task::JoinSet;
 use url::Url;
 
 use crate::{
-    app::show::bt_show::BtShower,
+    app::{
+        core::concurrency::ConcurrencyControl,
+        show::{bt_show::BtShower, common::ProgressFormat},
+        status::summary::Summary,
+    },
+    arguments::options::DownloadOptions,
     common::errors::{Error, Result},
-    features::{args::Args, running::Runnable},
+    features::running::{block_on, Runnable},
 };
 
 pub struct BtHandler {
@@ -21,37 +33,57 @@ pub struct BtHandler {
     peer_connect_timeout: Option<u64>,
     peer_read_write_timeout: Option<u64>,
     peer_keep_alive_interval: Option<u64>,
+    progress: ProgressFormat,
+    // Only for pausing, resuming, stopping the torrent and limiting the speed. Peers are managed by the session
+    control: ConcurrencyControl,
 }
 
 impl BtHandler {
-    pub fn new(args: &(impl Args + std::fmt::Debug)) -> BtHandler {
+    pub fn new(options: &DownloadOptions) -> Result<BtHandler> {
+        options.check()?;
         tracing::debug!("BtHandler::new");
 
-        BtHandler {
-            torrent_or_magnet: args.url(),
-            output: args.output(),
-            file_regex: args.bt_file_regex(),
-            seed: args.seed(),
-            trackers: args.bt_trackers(),
-            peer_connect_timeout: args.bt_peer_connect_timeout(),
-            peer_read_write_timeout: args.bt_peer_read_write_timeout(),
-            peer_keep_alive_interval: args.bt_peer_keep_alive_interval(),
-        }
+        let control = ConcurrencyControl::new(0, false);
+        control.set_limit(options.limit_rate().unwrap_or(0));
+
+        Ok(BtHandler {
+            torrent_or_magnet: options.url(),
+            output: options.output(),
+            file_regex: options.bt_file_regex(),
+            seed: options.seed(),
+            trackers: options.bt_trackers(),
+            peer_connect_timeout: options.bt_peer_connect_timeout(),
+            peer_read_write_timeout: options.bt_peer_read_write_timeout(),
+            peer_keep_alive_interval: options.bt_peer_keep_alive_interval(),
+            progress: options.progress(),
+            control,
+        })
     }
 
-    async fn start(self) -> Result<()> {
+    /// Use the control which is shared with other threads, to pause, resume or stop the task
+    pub fn with_control(mut self, control: ConcurrencyControl) -> BtHandler {
+        self.control = control;
+        self
+    }
+
+    /// Run the task on the current Tokio runtime. The status watcher is spawned as a task, which is
+    /// aborted when the future finishes or is dropped
+    pub async fn start(self) -> Result<Option<Summary>> {
         tracing::debug!("BtHandler::start");
 
         let output_dir = &self.output;
+        let name = output_dir
+            .file_name()
+            .ok_or_else(|| Error::InvalidPath(output_dir.display().to_string()))?;
         let persistence_dir = output_dir
             .join("..")
-            .join(output_dir.file_name().unwrap().to_string_lossy().to_string() + ".bt.aget");
+            .join(name.to_string_lossy().to_string() + ".bt.aget");
         let dht_config_filename = persistence_dir.join("dht.json");
 
         // 0. Check whether task is completed
         tracing::debug!("BtHandler: check whether task is completed");
         if output_dir.exists() && !persistence_dir.exists() {
-            return Ok(());
+            return Ok(None);
         }
 
         // 1. Create session
@@ -70,6 +102,10 @@ impl BtHandler {
                 keep_alive_interval: self.peer_keep_alive_interval.map(Duration::from_secs),
             }),
             fastresume: true,
+            ratelimits: LimitsConfig {
+                upload_bps: None,
+                download_bps: download_bps(self.control.limit()),
+            },
             persistence: Some(SessionPersistenceConfig::Json {
                 folder: Some(persistence_dir.clone()),
             }),
@@ -84,8 +120,12 @@ impl BtHandler {
         let stats_watcher = StatsWatcher {
             session: session.clone(),
             forever: self.seed,
+            progress: self.progress,
+            url: self.torrent_or_magnet.to_string(),
+            control: self.control.clone(),
         };
-        let stats_watcher_join_handler = actix_rt::spawn(stats_watcher.watch());
+        let mut watcher = JoinSet::new();
+        watcher.spawn(stats_watcher.watch());
 
         // 3. Add torrent or magnet
         tracing::debug!("BtHandler: add torrent or magnet");
@@ -103,63 +143,119 @@ impl BtHandler {
             .await
             .map_err(|err| Error::BitTorrentError(err.to_string()))?;
 
-        match response {
+        let handle = match response {
             AddTorrentResponse::AlreadyManaged(id, handle) => {
                 tracing::debug!("Torrent {} is already managed", id);
                 handle
-                    .wait_until_completed()
-                    .await
-                    .map_err(|err| Error::BitTorrentError(err.to_string()))?;
             }
             AddTorrentResponse::Added(id, handle) => {
                 tracing::debug!("Torrent {} is added", id);
                 handle
-                    .wait_until_completed()
-                    .await
-                    .map_err(|err| Error::BitTorrentError(err.to_string()))?;
             }
             _ => {
                 unreachable!()
             }
+        };
+
+        // Wait until the torrent is completed, and follow the control
+        let completed = handle.wait_until_completed().fuse();
+        pin_mut!(completed);
+        let mut paused = false;
+        let mut limit = self.control.limit();
+        loop {
+            select! {
+                result = completed => {
+                    result.map_err(|err| Error::BitTorrentError(err.to_string()))?;
+                    break;
+                }
+                _ = tokio::time::sleep(Duration::from_secs(1)).fuse() => {
+                    if self.control.is_stopped() {
+                        // The watcher must not use the session after it stops
+                        watcher.shutdown().await;
+                        stop_session(&session, &handle, paused).await;
+                        return Err(Error::Cancelled);
+                    }
+                    if self.control.is_paused() != paused {
+                        paused = !paused;
+                        let result = if paused {
+                            session.pause(&handle).await
+                        } else {
+                            session.unpause(&handle).await
+                        };
+                        result.map_err(|err| Error::BitTorrentError(err.to_string()))?;
+                    }
+                    if self.control.limit() != limit {
+                        limit = self.control.limit();
+                        session.ratelimits.set_download_bps(download_bps(limit));
+                    }
+                }
+            }
         }
 
-        // 4. Start seeding
+        // 4. Start seeding. The torrent is completed, so stopping ends the seeding normally
         if self.seed {
             tracing::debug!("BtHandler: start seeding");
-            println!("\nSeeding...");
-
-            loop {
-                actix_rt::time::sleep(Duration::from_secs(1)).await;
+            if matches!(self.progress, ProgressFormat::Bar | ProgressFormat::Plain) {
+                println!("\nSeeding...");
             }
+
+            self.control.wait_stopped().await;
         }
 
-        // 5. Exit shower
+        // 5. Exit shower, which exits when the torrent is finished or the seeding is stopped
         tracing::debug!("BtHandler: exit shower");
-        stats_watcher_join_handler.await.unwrap();
+        let summary = match watcher.join_next().await {
+            Some(Ok(summary)) => summary,
+            _ => return Err(Error::InnerError("The status watcher of the torrent fails".to_owned())),
+        };
+        if self.seed {
+            stop_session(&session, &handle, false).await;
+        }
 
         // 6. Remove persistence folder
         tracing::debug!("BtHandler: remove persistence folder");
         std::fs::remove_dir_all(persistence_dir)?;
 
-        Ok(())
+        Ok(Some(summary))
     }
 }
 
+/// Pause the torrent to write its state to the persistence folder, and stop the session, so the
+/// task can be resumed
+async fn stop_session(session: &Arc<Session>, handle: &Arc<ManagedTorrent>, paused: bool) {
+    if !paused {
+        if let Err(err) = session.pause(handle).await {
+            tracing::debug!("BtHandler: pause torrent: {:?}", err);
+        }
+    }
+    session.stop().await;
+}
+
+/// The download limit of the session, which is at most `u32::MAX` bytes per second
+fn download_bps(limit: u64) -> Option<NonZeroU32> {
+    NonZeroU32::new(limit.min(u32::MAX as u64) as u32)
+}
+
 impl Runnable for BtHandler {
-    fn run(self) -> Result<()> {
-        let sys = actix_rt::System::new();
-        sys.block_on(self.start())
+    fn run(self) -> Result<Option<Summary>> {
+        block_on(self.start())?
     }
 }
 
 struct StatsWatcher {
     session: Arc<Session>,
     forever: bool,
+    progress: ProgressFormat,
+    url: String,
+    control: ConcurrencyControl,
 }
 
 impl StatsWatcher {
-    async fn watch(self) {
-        let mut shower = BtShower::new();
+    /// Show the status until the torrent is finished, or the seeding is stopped, and return the
+    /// summary
+    async fn watch(self) -> Summary {
+        let mut shower = BtShower::new(self.progress);
+        let start = Instant::now();
 
         let tid = TorrentIdOrHash::Id(0);
         let api = Api::new(self.session.clone(), None);
@@ -169,29 +265,38 @@ impl StatsWatcher {
                 break torrent_details;
             }
 
-            actix_rt::time::sleep(Duration::from_secs(1)).await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
         };
 
         if self.forever {
             shower.print_msg("Seed the torrent. Press Ctrl+C to exit").unwrap();
         }
 
-        shower
-            .print_name(torrent_details.name.as_deref().unwrap_or("unknown"))
-            .expect("failed to print name");
-
         let torrent_files: Vec<_> = torrent_details.files.unwrap_or_default();
         let files: Vec<_> = torrent_files
             .iter()
             .map(|file| (file.name.as_str(), file.length, file.included))
             .collect();
 
-        shower.print_files(&files[..]).unwrap();
+        shower
+            .print_probe(torrent_details.name.as_deref().unwrap_or("unknown"), &files[..])
+            .expect("failed to print name");
 
         let mut completed_idx: Vec<bool> = vec![false; files.len()];
 
+        // Completed bytes at the first status, which are resumed, and the last status
+        let mut resumed: Option<u64> = None;
+        let mut last_completed = 0;
+        let mut peak_rate: f64 = 0.0;
+
         loop {
-            let stats = api.api_stats_v1(tid).expect("failed to get stats");
+            let stats = match api.api_stats_v1(tid) {
+                Ok(stats) => stats,
+                Err(err) => {
+                    tracing::debug!("StatsWatcher: get stats: {:?}", err);
+                    break;
+                }
+            };
 
             if let Some(live) = stats.live {
                 let completed = stats.progress_bytes;
@@ -200,6 +305,14 @@ impl StatsWatcher {
                 let up_rate = live.upload_speed.mbps * 1e6;
                 let uploaded = live.snapshot.uploaded_bytes;
 
+                self.control.set_progress(completed, total);
+                if resumed.is_some() {
+                    self.control.add_received(completed.saturating_sub(last_completed));
+                }
+                resumed.get_or_insert(completed);
+                last_completed = completed;
+                peak_rate = peak_rate.max(down_rate);
+
                 let eta = {
                     let remains = total - completed;
                     // rate > 1.0 for overflow
@@ -220,6 +333,13 @@ impl StatsWatcher {
                 let live_peers = peer_stats.live;
                 let queued_peers = peer_stats.queued;
 
+                let file_progress: Vec<_> = files
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, _, included))| *included)
+                    .map(|(i, (filename, length, _))| (*filename, stats.file_progress[i], *length))
+                    .collect();
+
                 shower
                     .print_status(
                         completed,
@@ -230,6 +350,7 @@ impl StatsWatcher {
                         uploaded,
                         live_peers,
                         queued_peers,
+                        &file_progress[..],
                     )
                     .unwrap();
 
@@ -237,18 +358,34 @@ impl StatsWatcher {
                     if *included && !completed_idx[i] {
                         let completed_size = stats.file_progress[i];
                         if completed_size == *length {
-                            shower.print_completed_file(filename).unwrap();
+                            shower.print_completed_file(filename, *length).unwrap();
                             completed_idx[i] = true;
                         }
                     }
                 })
             }
 
-            if !self.forever && stats.finished {
+            if (!self.forever && stats.finished) || (self.forever && self.control.is_stopped()) {
                 break;
             }
 
-            actix_rt::time::sleep(Duration::from_secs(1)).await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
         }
+
+        let elapsed = start.elapsed();
+        let received = last_completed - resumed.unwrap_or(0);
+        let average_rate = received as f64 / elapsed.as_secs_f64();
+        let summary = Summary {
+            url: self.url,
+            elapsed,
+            received,
+            average_rate,
+            peak_rate: peak_rate.max(average_rate),
+            // Pieces are verified and requested again by the session, which are not counted
+            retries: 0,
+            redownloaded: 0,
+        };
+        shower.print_summary(&summary).unwrap();
+        summary
     }
 }
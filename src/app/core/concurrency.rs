@@ -0,0 +1,424 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use crate::arguments::options::DownloadOptions;
+
+struct ControlInner {
+    target: AtomicU64,
+    active: AtomicU64,
+    // Active tasks which wait for a connection of the host
+    waiting: AtomicU64,
+    received: AtomicU64,
+    errors: AtomicU64,
+    retries: AtomicU64,
+    redownloaded: AtomicU64,
+    adaptive: bool,
+    completed: AtomicU64,
+    total: AtomicU64,
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    // Wake up paused tasks when they are resumed or stopped
+    resumed: Notify,
+    // Wake up running tasks when they are paused or stopped
+    interrupted: Notify,
+    // Bytes per second of all tasks, 0 for no limit
+    limit: AtomicU64,
+    // The time when the next bytes can be received under the limit
+    next: Mutex<Instant>,
+}
+
+/// The shared state which controls the number of running range tasks
+///
+/// Tasks are spawned until the number of active tasks reaches the target, and a task exits when
+/// the number of active tasks is over the target.
+///
+/// It is also the handle to pause, resume and stop a running task from other threads, e.g. the
+/// dashboard of `--tui`.
+#[derive(Clone)]
+pub struct ConcurrencyControl {
+    inner: Arc<ControlInner>,
+}
+
+impl ConcurrencyControl {
+    pub fn new(target: u64, adaptive: bool) -> ConcurrencyControl {
+        ConcurrencyControl {
+            inner: Arc::new(ControlInner {
+                target: AtomicU64::new(target.max(1)),
+                active: AtomicU64::new(0),
+                waiting: AtomicU64::new(0),
+                received: AtomicU64::new(0),
+                errors: AtomicU64::new(0),
+                retries: AtomicU64::new(0),
+                redownloaded: AtomicU64::new(0),
+                adaptive,
+                completed: AtomicU64::new(0),
+                total: AtomicU64::new(0),
+                paused: AtomicBool::new(false),
+                stopped: AtomicBool::new(false),
+                resumed: Notify::new(),
+                interrupted: Notify::new(),
+                limit: AtomicU64::new(0),
+                next: Mutex::new(Instant::now()),
+            }),
+        }
+    }
+
+    /// The control of a task which is run by several handlers, e.g. retries
+    pub fn from_options(options: &DownloadOptions) -> ConcurrencyControl {
+        let control = ConcurrencyControl::new(options.concurrency(), options.auto_concurrency());
+        control.set_limit(options.limit_rate().unwrap_or(0));
+        control
+    }
+
+    /// Whether the target is tuned by the throughput
+    pub fn is_adaptive(&self) -> bool {
+        self.inner.adaptive
+    }
+
+    pub fn target(&self) -> u64 {
+        self.inner.target.load(Ordering::Relaxed)
+    }
+
+    /// At least one task runs, so the task can finish
+    pub fn set_target(&self, target: u64) {
+        self.inner.target.store(target.max(1), Ordering::Relaxed);
+    }
+
+    pub fn active(&self) -> u64 {
+        self.inner.active.load(Ordering::Relaxed)
+    }
+
+    /// A task starts
+    pub fn enter(&self) {
+        self.inner.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A task exits
+    pub fn leave(&self) {
+        self.inner.active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The number of active tasks which wait for a connection, e.g. under `--max-connections-per-host`
+    pub fn waiting(&self) -> u64 {
+        self.inner.waiting.load(Ordering::Relaxed)
+    }
+
+    /// An active task starts to wait for a connection
+    pub fn start_waiting(&self) {
+        self.inner.waiting.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The task has the connection
+    pub fn stop_waiting(&self) {
+        self.inner.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The number of active tasks which have their connections
+    pub fn connected(&self) -> u64 {
+        self.active().saturating_sub(self.waiting())
+    }
+
+    /// Whether there are more active tasks than the target
+    pub fn is_over(&self) -> bool {
+        self.active() > self.target()
+    }
+
+    /// The number of received bytes
+    pub fn received(&self) -> u64 {
+        self.inner.received.load(Ordering::Relaxed)
+    }
+
+    pub fn add_received(&self, incr: u64) {
+        self.inner.received.fetch_add(incr, Ordering::Relaxed);
+    }
+
+    /// The number of errors which mean the server is overloaded, e.g. 429 and timeout
+    pub fn errors(&self) -> u64 {
+        self.inner.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn add_error(&self) {
+        self.inner.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of failed requests which are retried
+    pub fn retries(&self) -> u64 {
+        self.inner.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn add_retry(&self) {
+        self.inner.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of bytes which are received but dropped
+    pub fn redownloaded(&self) -> u64 {
+        self.inner.redownloaded.load(Ordering::Relaxed)
+    }
+
+    pub fn add_redownloaded(&self, incr: u64) {
+        self.inner.redownloaded.fetch_add(incr, Ordering::Relaxed);
+    }
+
+    /// Completed bytes, including the resumed part
+    pub fn completed(&self) -> u64 {
+        self.inner.completed.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes, 0 if it is unknown
+    pub fn total(&self) -> u64 {
+        self.inner.total.load(Ordering::Relaxed)
+    }
+
+    /// Receivers publish the progress for other threads
+    pub fn set_progress(&self, completed: u64, total: u64) {
+        self.inner.completed.store(completed, Ordering::Relaxed);
+        self.inner.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::Relaxed)
+    }
+
+    /// Tasks wait at their next chunk until they are resumed
+    pub fn pause(&self) {
+        self.inner.paused.store(true, Ordering::Relaxed);
+        self.inner.interrupted.notify_waiters();
+    }
+
+    pub fn resume(&self) {
+        self.inner.paused.store(false, Ordering::Relaxed);
+        self.inner.resumed.notify_waiters();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.inner.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Tasks exit at their next chunk, and the handler returns `Error::Cancelled`
+    pub fn stop(&self) {
+        self.inner.stopped.store(true, Ordering::Relaxed);
+        self.inner.resumed.notify_waiters();
+        self.inner.interrupted.notify_waiters();
+    }
+
+    /// The speed limit in bytes per second, 0 for no limit
+    pub fn limit(&self) -> u64 {
+        self.inner.limit.load(Ordering::Relaxed)
+    }
+
+    pub fn set_limit(&self, limit: u64) {
+        self.inner.limit.store(limit, Ordering::Relaxed);
+        // Forget the turns which are taken under the old limit
+        *self.inner.next.lock().unwrap() = Instant::now();
+    }
+
+    /// Wait until the received bytes are under the speed limit
+    ///
+    /// Each call takes the time that the bytes need under the limit, and waits for its turn.
+    pub async fn throttle(&self, incr: u64) {
+        let limit = self.limit();
+        if limit == 0 {
+            return;
+        }
+        let wait = {
+            let mut next = self.inner.next.lock().unwrap();
+            let now = Instant::now();
+            let turn = std::cmp::max(*next, now);
+            *next = turn + Duration::from_secs_f64(incr as f64 / limit as f64);
+            turn - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Wait until the task is stopped
+    pub async fn wait_stopped(&self) {
+        loop {
+            let notified = self.inner.resumed.notified();
+            if self.is_stopped() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Wait until the task is paused or stopped, e.g. while a response is stalled
+    pub async fn wait_interrupted(&self) {
+        loop {
+            let interrupted = self.inner.interrupted.notified();
+            if self.is_paused() || self.is_stopped() {
+                return;
+            }
+            interrupted.await;
+        }
+    }
+
+    /// Wait until the task is resumed or stopped
+    pub async fn wait_resumed(&self) {
+        loop {
+            // The notification is received once the future is created
+            let resumed = self.inner.resumed.notified();
+            if !self.is_paused() || self.is_stopped() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
+
+/// The interval of measuring the throughput
+const TUNE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Throughput must rise by this ratio to keep adding tasks
+const MIN_GAIN: f64 = 1.1;
+
+/// `AdaptiveTuner` tunes the target of a `ConcurrencyControl` by the throughput
+///
+/// It adds tasks while the throughput keeps rising, reverts the last increase when the throughput
+/// plateaus, and removes tasks when errors appear.
+pub struct AdaptiveTuner {
+    max: u64,
+    growing: bool,
+    previous_target: u64,
+    last_rate: f64,
+    last_received: u64,
+    last_errors: u64,
+    tick: Instant,
+}
+
+impl AdaptiveTuner {
+    pub fn new(max: u64) -> AdaptiveTuner {
+        AdaptiveTuner {
+            max,
+            growing: true,
+            previous_target: 0,
+            last_rate: 0.0,
+            last_received: 0,
+            last_errors: 0,
+            tick: Instant::now(),
+        }
+    }
+
+    /// Check the throughput and the errors, and change the target if needed
+    pub fn tune(&mut self, control: &ConcurrencyControl) {
+        let target = control.target();
+
+        let errors = control.errors();
+        if errors > self.last_errors {
+            self.last_errors = errors;
+            self.growing = false;
+            let new_target = std::cmp::max(1, target.saturating_sub(std::cmp::max(1, target / 4)));
+            if new_target != target {
+                tracing::debug!(
+                    "AdaptiveTuner: errors appear, concurrency: {} -> {}",
+                    target,
+                    new_target
+                );
+                control.set_target(new_target);
+            }
+            self.reset_window(control);
+            return;
+        }
+
+        if self.tick.elapsed() < TUNE_WINDOW {
+            return;
+        }
+        let rate = (control.received() - self.last_received) as f64 / self.tick.elapsed().as_secs_f64();
+        self.reset_window(control);
+
+        // The throughput is only comparable when all tasks are connected
+        if !self.growing || control.connected() < target {
+            return;
+        }
+
+        if self.last_rate == 0.0 || rate > self.last_rate * MIN_GAIN {
+            let new_target = std::cmp::min(self.max, target + std::cmp::max(1, target / 2));
+            if new_target == target {
+                self.growing = false;
+            } else {
+                tracing::debug!(
+                    "AdaptiveTuner: rate: {:.0}, concurrency: {} -> {}",
+                    rate,
+                    target,
+                    new_target
+                );
+                self.previous_target = target;
+                control.set_target(new_target);
+            }
+        } else {
+            self.growing = false;
+            if self.previous_target != 0 {
+                tracing::debug!(
+                    "AdaptiveTuner: rate plateaus: {:.0}, concurrency: {} -> {}",
+                    rate,
+                    target,
+                    self.previous_target
+                );
+                control.set_target(self.previous_target);
+            }
+        }
+        self.last_rate = rate;
+    }
+
+    fn reset_window(&mut self, control: &ConcurrencyControl) {
+        self.last_received = control.received();
+        self.tick = Instant::now();
+    }
+}
+
+/// Choose the chunk size by the content length, so that there are enough pairs for the maximum
+/// concurrency
+pub fn auto_chunk_size(content_length: u64, max_concurrency: u64) -> u64 {
+    const MIN_CHUNK_SIZE: u64 = 1024 * 1024; // 1m
+    const MAX_CHUNK_SIZE: u64 = 1024 * 1024 * 50; // 50m
+    (content_length / (max_concurrency * 4)).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveTuner, ConcurrencyControl, TUNE_WINDOW};
+
+    #[test]
+    fn test_target_is_at_least_one() {
+        let control = ConcurrencyControl::new(0, true);
+        assert_eq!(control.target(), 1);
+        control.set_target(0);
+        assert_eq!(control.target(), 1);
+
+        // Errors lower the target, but not below one
+        let mut tuner = AdaptiveTuner::new(32);
+        control.add_error();
+        tuner.tune(&control);
+        assert_eq!(control.target(), 1);
+    }
+
+    #[test]
+    fn test_waiting_tasks_are_not_connected() {
+        let control = ConcurrencyControl::new(2, true);
+        control.enter();
+        control.enter();
+        control.start_waiting();
+        assert_eq!((control.active(), control.connected()), (2, 1));
+
+        // The throughput isn't compared until all tasks are connected
+        let mut tuner = AdaptiveTuner::new(32);
+        tuner.tick -= TUNE_WINDOW;
+        control.add_received(1024);
+        tuner.tune(&control);
+        assert_eq!(control.target(), 2);
+
+        control.stop_waiting();
+        tuner.tick -= TUNE_WINDOW;
+        control.add_received(1024);
+        tuner.tune(&control);
+        assert_eq!(control.target(), 3);
+    }
+}
@@ -0,0 +1,577 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    select, FutureExt, Stream,
+};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+use crate::{
+    app::{
+        core::{
+            bt::BtHandler, concurrency::ConcurrencyControl, http::HttpHandler, m3u8::M3u8Handler, retry::RetryPolicy,
+        },
+        status::summary::Summary,
+    },
+    arguments::options::DownloadOptions,
+    common::{
+        errors::{Error, Result},
+        net::pool::SharedPool,
+        tasks::TaskType,
+    },
+};
+
+/// The interval of progress events
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Start downloads from an async program, e.g. a service on a Tokio runtime
+///
+/// Each task and its requests are spawned on the caller's runtime. The task is retried like `ag`.
+///
+/// ```no_run
+/// # async fn download() -> aget::common::errors::Result<()> {
+/// use aget::{DownloadOptions, Downloader};
+/// use futures::StreamExt;
+///
+/// let options = DownloadOptions::new("http://example.com/file.iso".parse()?).with_concurrency(4);
+/// let mut task = Downloader::new().download(options);
+/// while let Some(event) = task.next().await {
+///     println!("{:?}", event);
+/// }
+/// let summary = task.result().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    handle: Handle,
+}
+
+impl Default for Downloader {
+    fn default() -> Downloader {
+        Downloader::new()
+    }
+}
+
+impl Downloader {
+    /// Use the runtime of the caller
+    ///
+    /// # Panics
+    ///
+    /// It panics outside of a Tokio runtime, like `tokio::spawn`. Use `Downloader::try_new` or
+    /// `Downloader::with_handle` there.
+    pub fn new() -> Downloader {
+        Downloader {
+            handle: Handle::current(),
+        }
+    }
+
+    /// Use the runtime of the caller, or fail outside of a Tokio runtime
+    pub fn try_new() -> Result<Downloader> {
+        let handle = Handle::try_current().map_err(|err| Error::InnerError(err.to_string()))?;
+        Ok(Downloader { handle })
+    }
+
+    /// Use the runtime of the handle
+    pub fn with_handle(handle: Handle) -> Downloader {
+        Downloader { handle }
+    }
+
+    /// The runtime which tasks are spawned on
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Start the task of the options
+    pub fn download(&self, options: DownloadOptions) -> DownloadTask {
+        let control = ConcurrencyControl::from_options(&options);
+        self.download_with_control(options, control)
+    }
+
+    /// Start the task with a control which is created before, e.g. to cancel a pending task
+    pub fn download_with_control(&self, options: DownloadOptions, control: ConcurrencyControl) -> DownloadTask {
+        let (sender, events) = unbounded();
+
+        self.handle.spawn(send_progress(control.clone(), sender.clone()));
+
+        let result = {
+            let control = control.clone();
+            self.handle.spawn(async move {
+                let result = run_with_retries(&options, &control, |event| {
+                    let _ = sender.unbounded_send(event);
+                })
+                .await;
+                let _ = sender.unbounded_send(DownloadEvent::Progress(Progress::new(&control, 0.0)));
+                // The events end with the task
+                sender.close_channel();
+                result
+            })
+        };
+
+        DownloadTask {
+            control,
+            events,
+            result,
+        }
+    }
+}
+
+/// The handle of a running task
+///
+/// It is a stream of the events of the task, which ends when the task finishes. Dropping the
+/// handle doesn't stop the task.
+pub struct DownloadTask {
+    control: ConcurrencyControl,
+    events: UnboundedReceiver<DownloadEvent>,
+    result: JoinHandle<Result<Option<Summary>>>,
+}
+
+impl DownloadTask {
+    /// Pause, resume, stop the task, and change its concurrency and speed limit
+    pub fn control(&self) -> &ConcurrencyControl {
+        &self.control
+    }
+
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Stop the task after the received data is written, so it can be resumed by a new task. The
+    /// result is `Error::Cancelled`
+    pub fn cancel(&self) {
+        self.control.stop();
+    }
+
+    /// Wait until the task finishes. Return `None` if the task has been completed before
+    pub async fn result(self) -> Result<Option<Summary>> {
+        self.result
+            .await
+            .unwrap_or_else(|err| Err(Error::InnerError(format!("The task panics: {}", err))))
+    }
+}
+
+impl Stream for DownloadTask {
+    type Item = DownloadEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<DownloadEvent>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
+
+/// An event of a running task
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    /// The periodic progress, and the last one when the task finishes
+    Progress(Progress),
+
+    /// The task fails and will be retried after `wait`
+    Retry {
+        attempt: u64,
+        retries: u64,
+        wait: Duration,
+        error: String,
+    },
+}
+
+/// The progress of a running task
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Completed bytes, including the resumed part
+    pub completed: u64,
+    /// Total bytes, 0 if it is unknown
+    pub total: u64,
+    /// Bytes per second since the last progress
+    pub rate: f64,
+    /// The number of active connections
+    pub connections: u64,
+    pub paused: bool,
+}
+
+impl Progress {
+    fn new(control: &ConcurrencyControl, rate: f64) -> Progress {
+        Progress {
+            completed: control.completed(),
+            total: control.total(),
+            rate,
+            connections: control.connected(),
+            paused: control.is_paused(),
+        }
+    }
+}
+
+/// Send the progress periodically until the task finishes or the handle is dropped
+async fn send_progress(control: ConcurrencyControl, sender: UnboundedSender<DownloadEvent>) {
+    let mut last = (Instant::now(), control.received());
+    loop {
+        tokio::time::sleep(PROGRESS_INTERVAL).await;
+        if sender.is_closed() {
+            break;
+        }
+        let (now, received) = (Instant::now(), control.received());
+        let rate = received.saturating_sub(last.1) as f64 / now.duration_since(last.0).as_secs_f64();
+        last = (now, received);
+        let _ = sender.unbounded_send(DownloadEvent::Progress(Progress::new(&control, rate)));
+    }
+}
+
+/// Run the task until it succeeds, it is stopped or the retries are used up
+///
+/// `on_retry` receives `DownloadEvent::Retry` before the wait of each retry.
+pub async fn run_with_retries(
+    options: &DownloadOptions,
+    control: &ConcurrencyControl,
+    mut on_retry: impl FnMut(DownloadEvent),
+) -> Result<Option<Summary>> {
+    options.check()?;
+
+    // The deadline of `--max-time` starts with the task
+    let policy = RetryPolicy::new(options);
+    // Failed routes are still avoided by the retries
+    let pool = SharedPool::default();
+    let mut attempt = 0;
+    loop {
+        let result = match options.task_type() {
+            TaskType::HTTP => {
+                HttpHandler::new(options)?
+                    .with_control(control.clone())
+                    .with_deadline(policy.deadline())
+                    .with_pool(pool.clone())
+                    .start()
+                    .await
+            }
+            TaskType::M3U8 => {
+                M3u8Handler::new(options)?
+                    .with_control(control.clone())
+                    .with_deadline(policy.deadline())
+                    .start()
+                    .await
+            }
+            TaskType::BT => BtHandler::new(options)?.with_control(control.clone()).start().await,
+        };
+        let err = match result {
+            Err(err) => err,
+            result => return result,
+        };
+        attempt += 1;
+        let wait = match policy.retry(attempt, &err) {
+            Some(wait) => wait,
+            None => return Err(err),
+        };
+        tracing::debug!("The task fails, retry after {:?}: {:?}", wait, err);
+
+        // The persistent DHT can't be loaded again, so it is removed
+        if let Error::BitTorrentError(ref msg) = err {
+            let output_dir = options.output();
+            if let (true, Some(name)) = (msg == "error initializing persistent DHT", output_dir.file_name()) {
+                let dht_file = output_dir
+                    .join("..")
+                    .join(name.to_string_lossy().to_string() + ".bt.aget")
+                    .join("dht.json");
+                if dht_file.exists() {
+                    std::fs::remove_file(dht_file).unwrap_or(());
+                }
+            }
+        }
+
+        on_retry(DownloadEvent::Retry {
+            attempt,
+            retries: policy.retries(),
+            wait,
+            error: err.to_string(),
+        });
+
+        // Stop waiting when the task is stopped
+        select! {
+            _ = tokio::time::sleep(wait).fuse() => {},
+            _ = control.wait_stopped().fuse() => return Err(Error::Cancelled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use futures::StreamExt;
+
+    use super::{DownloadEvent, Downloader};
+    use crate::{
+        arguments::options::DownloadOptions,
+        common::{errors::Error, net::Url},
+    };
+
+    /// Serve the body with ranges on a local port. The first `failures` requests are answered
+    /// with 503, and with `stall`, the responses of ranges hang after their first bytes.
+    ///
+    /// The heads of the requests are recorded in lowercase.
+    fn serve(body: Vec<u8>, failures: usize, stall: bool) -> (Url, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let (body, count) = (Arc::new(body), Arc::new(AtomicUsize::new(0)));
+        let requests = Arc::new(Mutex::new(vec![]));
+        {
+            let requests = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let (body, count, requests) = (body.clone(), count.clone(), requests.clone());
+                    thread::spawn(move || {
+                        let fail = count.fetch_add(1, Ordering::SeqCst) < failures;
+                        respond(stream, &body, fail, stall, &requests)
+                    });
+                }
+            });
+        }
+        (url, requests)
+    }
+
+    /// Redirect all requests to the url from another local port, which is another origin
+    fn redirect(to: &Url) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let head = format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            to
+        );
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                if read_head(&mut stream).is_some() {
+                    let _ = stream.write_all(head.as_bytes());
+                }
+            }
+        });
+        url
+    }
+
+    fn read_head(stream: &mut TcpStream) -> Option<String> {
+        let mut request = vec![];
+        let mut buf = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+        Some(String::from_utf8_lossy(&request).to_lowercase())
+    }
+
+    fn respond(mut stream: TcpStream, body: &[u8], fail: bool, stall: bool, requests: &Mutex<Vec<String>>) {
+        let request = match read_head(&mut stream) {
+            Some(request) => request,
+            None => return,
+        };
+        requests.lock().unwrap().push(request.clone());
+        if fail {
+            let _ =
+                stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            return;
+        }
+
+        let (head, part) = range_response(body, &request);
+        let _ = stream.write_all(head.as_bytes());
+        // The probe of the content length is answered
+        if stall && part.len() > 2 {
+            let _ = stream.write_all(&part[..1024]);
+            thread::sleep(Duration::from_secs(60));
+            return;
+        }
+        let _ = stream.write_all(part);
+    }
+
+    /// The head and the body of the response of the range in the request
+    fn range_response<'a>(body: &'a [u8], request: &str) -> (String, &'a [u8]) {
+        let last = body.len() - 1;
+        let (begin, end) = request
+            .lines()
+            .find_map(|line| line.strip_prefix("range: bytes="))
+            .and_then(|range| range.trim().split_once('-'))
+            .map(|(begin, end)| (begin.parse::<usize>().unwrap(), end.parse::<usize>().unwrap_or(last)))
+            .unwrap_or((0, last));
+        let part = &body[begin..=end.min(last)];
+        let head = format!(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            begin,
+            begin + part.len() - 1,
+            body.len(),
+            part.len()
+        );
+        (head, part)
+    }
+
+    fn output(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("aget-downloader-{}-{}", std::process::id(), name))
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_download_retries() {
+        let body: Vec<u8> = (0..100 * 1024).map(|i| (i % 251) as u8).collect();
+        let (url, _) = serve(body.clone(), 1, false);
+        let path = output("retries");
+
+        let (events, result) = runtime().block_on(async {
+            let options = DownloadOptions::new(url)
+                .with_output(&path)
+                .with_concurrency(1)
+                .with_retry_wait(0);
+            let mut task = Downloader::new().download(options);
+            let mut events = vec![];
+            while let Some(event) = task.next().await {
+                events.push(event);
+            }
+            (events, task.result().await)
+        });
+
+        // The first request fails, and the task succeeds after a retry
+        let retries: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                DownloadEvent::Retry { attempt, error, .. } => Some((*attempt, error.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].0, 1);
+        assert!(retries[0].1.contains("503"));
+        match events.last() {
+            Some(DownloadEvent::Progress(progress)) => assert_eq!(progress.completed, body.len() as u64),
+            event => panic!("The last event is not the progress: {:?}", event),
+        }
+        assert!(result.unwrap().is_some());
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_download_cancel() {
+        let body = vec![0u8; 1024 * 1024];
+        let (url, _) = serve(body, 0, true);
+        let path = output("cancel");
+
+        let result = runtime().block_on(async {
+            let options = DownloadOptions::new(url).with_output(&path).with_concurrency(1);
+            let mut task = Downloader::new().download(options);
+            // The response hangs after the first progress
+            assert!(matches!(task.next().await, Some(DownloadEvent::Progress(_))));
+            task.cancel();
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while task.next().await.is_some() {}
+                task.result().await
+            })
+            .await
+        });
+
+        // The stalled response doesn't hold the task, and the received data is kept for resuming
+        assert!(matches!(result, Ok(Err(Error::Cancelled))));
+        assert!(PathBuf::from(format!("{}.rc.aget", path.display())).exists());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(format!("{}.rc.aget", path.display())).unwrap();
+    }
+
+    #[test]
+    fn test_download_redirect_credentials() {
+        let body: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        for trusted in [false, true] {
+            let (url, requests) = serve(body.clone(), 0, false);
+            let path = output(&format!("redirect-{}", trusted));
+
+            let result = runtime().block_on(async {
+                let options = DownloadOptions::new(redirect(&url))
+                    .with_output(&path)
+                    .with_concurrency(2)
+                    .with_header("Authorization", "Bearer secret")
+                    .with_header("Cookie", "session=secret")
+                    .with_location_trusted(trusted);
+                Downloader::new().download(options).result().await
+            });
+            assert!(result.unwrap().is_some());
+            assert_eq!(std::fs::read(&path).unwrap(), body);
+
+            // The credentials are sent to the other origin only if the location is trusted
+            let requests = requests.lock().unwrap();
+            assert!(requests.len() > 1);
+            for request in requests.iter() {
+                assert_eq!(request.contains("authorization: bearer secret"), trusted);
+                assert_eq!(request.contains("cookie: session=secret"), trusted);
+            }
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_download_unix_socket() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::UnixListener,
+        };
+
+        let body: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+        let (socket, path) = (output("unix.sock"), output("unix"));
+        let _ = std::fs::remove_file(&socket);
+
+        let result = runtime().block_on(async {
+            // Serve the ranges over the unix socket
+            let listener = UnixListener::bind(&socket).unwrap();
+            let served = Arc::new(body.clone());
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let body = served.clone();
+                    tokio::spawn(async move {
+                        let mut request = vec![];
+                        let mut buf = [0u8; 1024];
+                        while !request.ends_with(b"\r\n\r\n") {
+                            match stream.read(&mut buf).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => request.extend_from_slice(&buf[..n]),
+                            }
+                        }
+                        let (head, part) = range_response(&body, &String::from_utf8_lossy(&request).to_lowercase());
+                        let _ = stream.write_all(head.as_bytes()).await;
+                        let _ = stream.write_all(part).await;
+                    });
+                }
+            });
+
+            // The host of the url is not resolved
+            let options = DownloadOptions::new("http://unix-socket.invalid/file".parse().unwrap())
+                .with_output(&path)
+                .with_concurrency(4)
+                .with_chunk_size(16 * 1024)
+                .with_unix_socket(&socket);
+            Downloader::new().download(options).result().await
+        });
+
+        assert!(result.unwrap().is_some());
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&socket).unwrap();
+    }
+}
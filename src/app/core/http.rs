@@ -1,49 +1,88 @@
 use std::{
     fmt,
     path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use futures::{
-    channel::mpsc::{channel, Sender},
-    pin_mut, select, SinkExt, StreamExt,
+    channel::mpsc::{channel, unbounded, Sender},
+    pin_mut, select, FutureExt, SinkExt, StreamExt,
 };
+use tokio::task::JoinSet;
 
 use crate::{
     app::{
+        core::{
+            concurrency::{auto_chunk_size, AdaptiveTuner, ConcurrencyControl},
+            limits::DownloadLimits,
+        },
         receive::http_receiver::HttpReceiver,
         record::{common::RECORDER_FILE_SUFFIX, range_recorder::RangeRecorder},
+        show::common::ProgressFormat,
+        status::{
+            connection_status::{Connection, ConnectionStatus},
+            summary::Summary,
+        },
     },
+    arguments::options::DownloadOptions,
     common::{
         bytes::bytes_type::Bytes,
         errors::{Error, Result},
         file::File,
         net::{
-            net::{build_http_client, redirect_and_contentlength, request},
-            ContentLengthValue, HttpClient, Method, Url,
+            byteranges::{multipart_boundary, parse_content_range, ByteRangesParser},
+            host_limit::HostLimit,
+            net::{
+                build_http2_clients, build_http_client, build_proxy_clients, build_spread_clients, is_identity,
+                redirect_and_contentlength, request, request_ranges, HttpClientOptions,
+            },
+            pool::{ClientPool, SharedPool},
+            ContentLengthValue, HttpClient, HttpVersion, Method, Url,
         },
         range::{split_pair, RangePair, SharedRangList},
         time::interval_stream,
     },
-    features::{args::Args, running::Runnable, stack::StackLike},
+    features::{
+        running::{block_on, Runnable},
+        stack::StackLike,
+    },
 };
 
 /// Http task handler
-pub struct HttpHandler<'a> {
+pub struct HttpHandler {
     output: PathBuf,
     method: Method,
     url: Url,
-    headers: Vec<(&'a str, &'a str)>,
-    data: Option<&'a str>,
+    headers: Vec<(String, String)>,
+    data: Option<String>,
     concurrency: u64,
+    auto_concurrency: bool,
+    max_concurrency: u64,
+    max_connections_per_host: Option<u64>,
     chunk_size: u64,
-    proxy: Option<&'a str>,
+    auto_chunk_size: bool,
+    max_ranges: u64,
+    proxy: Option<String>,
+    proxies: Vec<String>,
     timeout: Duration,
+    spread_addrs: bool,
+    http2_connections: u64,
+    client_options: HttpClientOptions,
     client: HttpClient,
+    progress: ProgressFormat,
+    verbose: bool,
+    // The number of active tasks. For `-s auto`, the target is tuned by the throughput.
+    control: ConcurrencyControl,
+    limits: DownloadLimits,
+    // The routes of range requests, which are kept across retries
+    pool: SharedPool,
 }
 
-impl<'a> std::fmt::Debug for HttpHandler<'a> {
+impl std::fmt::Debug for HttpHandler {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -53,58 +92,111 @@ impl<'a> std::fmt::Debug for HttpHandler<'a> {
     }
 }
 
-impl<'a> HttpHandler<'a> {
-    pub fn new(args: &(impl Args + std::fmt::Debug)) -> Result<HttpHandler<'_>> {
-        let headers = args.headers();
-        let timeout = args.timeout();
-        let dns_timeout = args.dns_timeout();
-        let keep_alive = args.keep_alive();
-        let skip_verify_tls_cert = args.skip_verify_tls_cert();
-        let proxy = args.proxy();
+impl HttpHandler {
+    pub fn new(options: &DownloadOptions) -> Result<HttpHandler> {
+        options.check()?;
+        let headers = options
+            .headers()
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        let timeout = options.timeout();
+        let proxy = options.proxy().map(|v| v.to_owned());
 
-        let client = build_http_client(&headers, timeout, dns_timeout, keep_alive, skip_verify_tls_cert, proxy)?;
+        let client_options = HttpClientOptions::from_options(options);
+        let client = build_http_client(&client_options)?;
+
+        let control = ConcurrencyControl::new(options.concurrency(), options.auto_concurrency());
+        control.set_limit(options.limit_rate().unwrap_or(0));
 
         tracing::debug!("HttpHandler::new");
 
         Ok(HttpHandler {
-            output: args.output(),
-            method: args.method(),
-            url: args.url(),
+            output: options.output(),
+            method: options.method(),
+            url: options.url(),
             headers,
-            data: args.data(),
-            concurrency: args.concurrency(),
-            chunk_size: args.chunk_size(),
+            data: options.data().map(|v| v.to_owned()),
+            concurrency: options.concurrency(),
+            auto_concurrency: options.auto_concurrency(),
+            max_concurrency: options.max_concurrency(),
+            max_connections_per_host: options.max_connections_per_host(),
+            chunk_size: options.chunk_size(),
+            auto_chunk_size: options.auto_chunk_size(),
+            max_ranges: options.max_ranges(),
             proxy,
+            proxies: options.proxies(),
             timeout,
+            spread_addrs: options.spread_addrs(),
+            http2_connections: options.http2_connections(),
+            client_options,
             client,
+            progress: options.progress(),
+            verbose: options.verbose(),
+            control,
+            limits: DownloadLimits::new(options),
+            pool: SharedPool::default(),
         })
     }
 
-    async fn start(mut self) -> Result<()> {
+    /// Use the pool of a previous attempt of the task, so the failed routes are still avoided
+    pub fn with_pool(mut self, pool: SharedPool) -> HttpHandler {
+        self.pool = pool;
+        self
+    }
+
+    /// Use the control which is shared with other threads, to pause, resume or stop the task
+    pub fn with_control(mut self, control: ConcurrencyControl) -> HttpHandler {
+        self.control = control;
+        self
+    }
+
+    /// Use the deadline of `--max-time` which starts before the first try of the task
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> HttpHandler {
+        self.limits = self.limits.with_deadline(deadline);
+        self
+    }
+
+    /// Run the task on the current Tokio runtime. Requests are spawned as tasks, which are aborted
+    /// when the future finishes or is dropped
+    pub async fn start(mut self) -> Result<Option<Summary>> {
         tracing::debug!("HttpHandler::start");
 
         // 0. Check whether task is completed
         tracing::debug!("HttpHandler: check whether task is completed");
         let mut rangerecorder = RangeRecorder::new(&*(self.output.to_string_lossy() + RECORDER_FILE_SUFFIX))?;
         if self.output.exists() && !rangerecorder.exists() {
-            return Ok(());
+            return Ok(None);
         }
 
         // 1. redirect and get content_length
         tracing::debug!("HttpHandler: redirect and content_length start");
-        let (url, cl) = redirect_and_contentlength(
-            &self.client,
-            self.method.clone(),
-            self.url.clone(),
-            self.data.map(|v| v.to_string()),
-        )
-        .await?;
+        let (url, cl) = self
+            .limits
+            .within(redirect_and_contentlength(
+                &self.client_options,
+                self.method.clone(),
+                self.url.clone(),
+                self.data.clone(),
+            ))
+            .await?;
         tracing::debug!("HttpHandler: redirect to: {}", url);
         tracing::debug!("HttpHandler: content_length: {:?}", cl);
 
+        // Reject a large file before anything is downloaded
+        if let ContentLengthValue::DirectLength(l) | ContentLengthValue::RangeLength(l) = cl {
+            self.limits.check_filesize(l)?;
+        }
+
+        // Don't send credentials to the other origin
+        if !self.client_options.location_trusted && url.origin() != self.url.origin() {
+            self.client_options = self.client_options.without_credentials();
+            self.client = build_http_client(&self.client_options)?;
+        }
+
         self.url = url;
 
-        let content_length = {
+        let mut content_length = {
             match cl {
                 ContentLengthValue::DirectLength(l) => l,
                 ContentLengthValue::RangeLength(l) => l,
@@ -115,13 +207,16 @@ impl<'a> HttpHandler<'a> {
         // 2. Compare recorded content length with the above one
         tracing::debug!("HttpHandler: compare recorded content length");
         let mut direct = true;
-        if let ContentLengthValue::RangeLength(cl) = cl {
+        // The decoded length is unknown for compressed responses, so download in a single stream
+        if self.client_options.compressed {
+            content_length = 0;
+        } else if let ContentLengthValue::RangeLength(cl) = cl {
             if self.output.exists() {
                 if rangerecorder.exists() {
                     rangerecorder.open()?;
                 } else {
                     // Task is completed
-                    return Ok(());
+                    return Ok(None);
                 }
             } else {
                 // Init rangerecorder
@@ -140,7 +235,7 @@ impl<'a> HttpHandler<'a> {
             else if pre_cl == 0 && pre_cl == cl {
                 File::new(&self.output, true)?.open()?;
                 rangerecorder.remove()?;
-                return Ok(());
+                return Ok(None);
             }
             // Content length is not consistent
             else if pre_cl != 0 && pre_cl != cl {
@@ -157,31 +252,47 @@ impl<'a> HttpHandler<'a> {
         let (sender, receiver) = channel::<(RangePair, Bytes)>(self.concurrency as usize + 10);
         let runtime_error: Arc<Mutex<Option<Error>>> = Arc::new(Mutex::new(None));
 
+        let control = self.control.clone();
+        // The status of each range request task
+        let connections = ConnectionStatus::new();
+
         // 4. Dispatch Task
         tracing::debug!("HttpHandler: dispatch task: direct: {}", direct);
+        let mut tasks = JoinSet::new();
         if direct {
             // We need a new `HttpClient` which has unlimited life time for `DirectRequestTask`
             let mut task = DirectRequestTask::new(
                 self.client.clone(),
                 self.method.clone(),
                 self.url.clone(),
-                self.data.map(|v| v.to_string()),
+                self.data.clone(),
                 sender.clone(),
+                control.clone(),
             );
             let runtime_error_clone = runtime_error.clone();
-            actix_rt::spawn(async move {
+            let control = control.clone();
+            control.enter();
+            tasks.spawn(async move {
                 if let Err(err) = task.start().await {
                     if runtime_error_clone.lock().unwrap().is_none() {
                         *runtime_error_clone.lock().unwrap() = Some(err);
                     }
                 }
+                control.leave();
             });
         } else {
+            let chunk_size = if self.auto_chunk_size {
+                auto_chunk_size(content_length, self.max_concurrency)
+            } else {
+                self.chunk_size
+            };
+            tracing::debug!("HttpHandler: chunk size: {}", chunk_size);
+
             // Make range pairs stack
             let mut stack = vec![];
             let gaps = rangerecorder.gaps()?;
             for gap in gaps.iter() {
-                let mut list = split_pair(gap, self.chunk_size);
+                let mut list = split_pair(gap, chunk_size);
                 stack.append(&mut list);
             }
             stack.reverse();
@@ -189,49 +300,143 @@ impl<'a> HttpHandler<'a> {
             // let stack = SharedRangList::new(rangerecorder.gaps()?);
             tracing::debug!("HttpHandler: range stack length: {}", stack.len());
 
-            let concurrency = std::cmp::min(stack.len() as u64, self.concurrency);
-            for i in 1..concurrency + 1 {
-                let mut task = RangeRequestTask::new(
-                    self.client.clone(),
-                    self.method.clone(),
-                    self.url.clone(),
-                    self.data.map(|v| v.to_string()),
-                    stack.clone(),
-                    sender.clone(),
-                    i,
-                    self.timeout,
-                );
-                let runtime_error_clone = runtime_error.clone();
-                actix_rt::spawn(async move {
-                    if let Err(err) = task.start().await {
-                        if runtime_error_clone.lock().unwrap().is_none() {
-                            *runtime_error_clone.lock().unwrap() = Some(err);
-                        }
+            // Rotate range requests across the proxies, or spread them across all addresses of the server,
+            // or multiplex them over several HTTP/2 connections
+            let pool = match self.pool.get() {
+                Some(pool) => {
+                    pool.revive();
+                    pool
+                }
+                None => {
+                    let pool = if self.proxies.len() > 1 {
+                        ClientPool::proxies(build_proxy_clients(&self.client_options, &self.proxies)?)
+                    } else if self.spread_addrs {
+                        ClientPool::new(build_spread_clients(&self.client_options, &self.url).await?)
+                    } else if self.client_options.http_version != HttpVersion::Http1 && self.http2_connections > 1 {
+                        ClientPool::new(build_http2_clients(
+                            &self.client_options,
+                            &self.client,
+                            self.http2_connections,
+                        )?)
+                    } else {
+                        ClientPool::single(self.client.clone())
+                    };
+                    self.pool.set(pool.clone());
+                    pool
+                }
+            };
+
+            // Whether small ranges are coalesced into multi-range requests. It is turned off once the
+            // server refuses them
+            let multi_range = Arc::new(AtomicBool::new(self.max_ranges > 1));
+
+            // Spawn tasks until the target concurrency, and respawn them if pairs are pushed back
+            let (control, connections) = (control.clone(), connections.clone());
+            let mut tuner = if self.auto_concurrency {
+                Some(AdaptiveTuner::new(self.max_concurrency))
+            } else {
+                None
+            };
+            let (method, url, data) = (self.method.clone(), self.url.clone(), self.data.clone());
+            let (timeout, max_ranges) = (self.timeout, self.max_ranges);
+            // Range requests of all handlers to the host wait for the shared limit
+            let host_limit = self.max_connections_per_host.map(|limit| HostLimit::new(&url, limit));
+            let (sender, runtime_error) = (sender.clone(), runtime_error.clone());
+            // Tasks wake up the dispatcher when they exit
+            let (waker, mut wakeup) = unbounded::<()>();
+            tasks.spawn(async move {
+                // Range tasks are aborted with the dispatcher
+                let mut range_tasks = JoinSet::new();
+                let mut id = 0;
+                loop {
+                    while range_tasks.try_join_next().is_some() {}
+                    if let Some(tuner) = tuner.as_mut() {
+                        tuner.tune(&control);
                     }
-                });
-            }
+                    if runtime_error.lock().unwrap().is_some()
+                        || ((stack.len() == 0 || control.is_stopped()) && control.active() == 0)
+                    {
+                        break;
+                    }
+
+                    while !control.is_stopped()
+                        && control.active() < control.target()
+                        && control.active() < stack.len() as u64
+                    {
+                        id += 1;
+                        let mut task = RangeRequestTask::new(
+                            pool.clone(),
+                            pool.route(id as usize - 1),
+                            method.clone(),
+                            url.clone(),
+                            data.clone(),
+                            stack.clone(),
+                            sender.clone(),
+                            id,
+                            timeout,
+                            chunk_size,
+                            max_ranges,
+                            multi_range.clone(),
+                            control.clone(),
+                            host_limit.clone(),
+                            connections.open(id),
+                        );
+                        control.enter();
+                        let (control, runtime_error, waker) = (control.clone(), runtime_error.clone(), waker.clone());
+                        range_tasks.spawn(async move {
+                            if let Err(err) = task.start().await {
+                                if runtime_error.lock().unwrap().is_none() {
+                                    *runtime_error.lock().unwrap() = Some(err);
+                                }
+                            }
+                            control.leave();
+                            let _ = waker.unbounded_send(());
+                        });
+                    }
+
+                    select! {
+                        _ = wakeup.next() => {},
+                        _ = tokio::time::sleep(Duration::from_secs(1)).fuse() => {},
+                    }
+                }
+            });
         }
         drop(sender); // Remove the reference and let `Task` to handle it
 
         // 5. Create receiver
         tracing::debug!("HttpHandler: create receiver");
-        let mut httpreceiver = HttpReceiver::new(&self.output, direct, content_length)?;
+        let mut httpreceiver = HttpReceiver::new(
+            &self.output,
+            direct,
+            content_length,
+            control,
+            connections,
+            self.progress,
+            self.verbose,
+        )?
+        .with_limits(self.limits.clone());
         httpreceiver.start(receiver).await?;
 
         if let Some(err) = runtime_error.lock().unwrap().take() {
             return Err(err);
         }
+        // The received ranges are recorded, so the task can be resumed
+        if self.control.is_stopped() {
+            return Err(Error::Cancelled);
+        }
 
         // 6. Task succeeds. Remove rangerecorder file
         rangerecorder.remove().unwrap_or(()); // Missing error
-        Ok(())
+
+        let summary = httpreceiver.summary(self.url.as_str());
+        httpreceiver.show_summary(&summary)?;
+        Ok(Some(summary))
     }
 }
 
-impl<'a> Runnable for HttpHandler<'a> {
-    fn run(self) -> Result<()> {
-        let sys = actix_rt::System::new();
-        sys.block_on(self.start())
+impl Runnable for HttpHandler {
+    fn run(self) -> Result<Option<Summary>> {
+        block_on(self.start())?
     }
 }
 
@@ -242,16 +447,18 @@ struct DirectRequestTask {
     url: Url,
     data: Option<String>,
     sender: Sender<(RangePair, Bytes)>,
+    control: ConcurrencyControl,
 }
 
 impl DirectRequestTask {
-    #[tracing::instrument(skip(client, sender))]
+    #[tracing::instrument(skip(client, sender, control))]
     fn new(
         client: HttpClient,
         method: Method,
         url: Url,
         data: Option<String>,
         sender: Sender<(RangePair, Bytes)>,
+        control: ConcurrencyControl,
     ) -> DirectRequestTask {
         DirectRequestTask {
             client,
@@ -259,11 +466,29 @@ impl DirectRequestTask {
             url,
             data,
             sender,
+            control,
         }
     }
 
     #[tracing::instrument(skip(self))]
     async fn start(&mut self) -> Result<()> {
+        let mut offset = 0u64;
+        loop {
+            // The connection is closed while the task is paused
+            self.control.wait_resumed().await;
+            if self.control.is_stopped() || !self.req(&mut offset).await? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Request the resource and send the data after the offset, returning whether the response is
+    /// interrupted by pausing
+    ///
+    /// The response can't be resumed by ranges, so the data before the offset is received again and
+    /// dropped.
+    async fn req(&mut self, offset: &mut u64) -> Result<bool> {
         let resp = request(
             &self.client,
             self.method.clone(),
@@ -279,18 +504,35 @@ impl DirectRequestTask {
         let resp = resp.unwrap();
         let mut stream = resp.bytes_stream();
 
-        let mut offset = 0u64;
-        while let Some(item) = stream.next().await {
+        let mut skip = *offset;
+        loop {
+            // Don't wait for the next chunk of a stalled response when the task is paused or stopped
+            let item = select! {
+                item = stream.next().fuse() => item,
+                _ = self.control.wait_interrupted().fuse() => return Ok(!self.control.is_stopped()),
+            };
+            let item = match item {
+                Some(item) => item,
+                None => break,
+            };
             match item {
-                Ok(chunk) => {
+                Ok(mut chunk) => {
+                    if skip > 0 {
+                        let n = skip.min(chunk.len() as u64);
+                        self.control.add_redownloaded(n);
+                        skip -= n;
+                        chunk = chunk.slice(n as usize..);
+                    }
                     let len = chunk.len();
                     if len == 0 {
                         continue;
                     }
 
-                    let pair = RangePair::new(offset, offset + len as u64 - 1); // The pair is a closed interval
+                    let pair = RangePair::new(*offset, *offset + len as u64 - 1); // The pair is a closed interval
                     self.sender.send((pair, chunk)).await.unwrap();
-                    offset += len as u64;
+                    *offset += len as u64;
+                    self.control.add_received(len as u64);
+                    self.control.throttle(len as u64).await;
                 }
                 Err(err) => {
                     tracing::error!("DirectRequestTask read error: {:?}", err);
@@ -299,13 +541,14 @@ impl DirectRequestTask {
             }
         }
 
-        Ok(())
+        Ok(false)
     }
 }
 
 /// Request the resource with a range header which is in the `SharedRangList`
 struct RangeRequestTask {
-    client: HttpClient,
+    pool: ClientPool,
+    route: usize,
     method: Method,
     url: Url,
     data: Option<String>,
@@ -313,12 +556,19 @@ struct RangeRequestTask {
     sender: Sender<(RangePair, Bytes)>,
     id: u64,
     timeout: Duration,
+    chunk_size: u64,
+    max_ranges: u64,
+    multi_range: Arc<AtomicBool>,
+    control: ConcurrencyControl,
+    host_limit: Option<HostLimit>,
+    connection: Connection,
 }
 
 impl RangeRequestTask {
-    #[tracing::instrument(skip(client, sender))]
+    #[tracing::instrument(skip(pool, sender, control, host_limit, connection))]
     fn new(
-        client: HttpClient,
+        pool: ClientPool,
+        route: usize,
         method: Method,
         url: Url,
         data: Option<String>,
@@ -326,9 +576,16 @@ impl RangeRequestTask {
         sender: Sender<(RangePair, Bytes)>,
         id: u64,
         timeout: Duration,
+        chunk_size: u64,
+        max_ranges: u64,
+        multi_range: Arc<AtomicBool>,
+        control: ConcurrencyControl,
+        host_limit: Option<HostLimit>,
+        connection: Connection,
     ) -> RangeRequestTask {
         RangeRequestTask {
-            client,
+            pool,
+            route,
             method,
             url,
             data,
@@ -336,21 +593,123 @@ impl RangeRequestTask {
             sender,
             id,
             timeout,
+            chunk_size,
+            max_ranges,
+            multi_range,
+            control,
+            host_limit,
+            connection,
+        }
+    }
+
+    /// Pop following small pairs to coalesce them with the pair into one multi-range request
+    ///
+    /// A pair is small if it is shorter than `chunk_size`, and the total length of coalesced pairs
+    /// is not more than `chunk_size`.
+    fn coalesce(&mut self, pair: RangePair) -> Vec<RangePair> {
+        let mut pairs = vec![pair];
+        if !self.multi_range.load(Ordering::Relaxed) || pair.length() >= self.chunk_size {
+            return pairs;
         }
+
+        let mut total = pair.length();
+        while (pairs.len() as u64) < self.max_ranges {
+            match self.stack.pop() {
+                Some(next) if total + next.length() <= self.chunk_size => {
+                    total += next.length();
+                    pairs.push(next);
+                }
+                Some(next) => {
+                    self.stack.push(next);
+                    break;
+                }
+                None => break,
+            }
+        }
+        pairs
     }
 
     #[tracing::instrument(skip(self))]
     async fn start(&mut self) -> Result<()> {
         tracing::debug!("Fire RangeRequestTask: {}", self.id);
-        while let Some(pair) = self.stack.pop() {
-            match self.req(pair).await {
-                // Exit whole process when `Error::InnerError` is returned
+        loop {
+            // Hold on while the task is paused, and exit when it is stopped
+            self.control.wait_resumed().await;
+            if self.control.is_stopped() {
+                tracing::debug!("RangeRequestTask {}: exit for stopping", self.id);
+                break;
+            }
+            // Exit when there are too many tasks
+            if self.control.is_over() {
+                tracing::debug!("RangeRequestTask {}: exit for the concurrency", self.id);
+                break;
+            }
+            // Hold a connection of the host until the request is done. The task isn't connected
+            // until then
+            let _permit = match &self.host_limit {
+                Some(host_limit) => {
+                    self.control.start_waiting();
+                    let permit = select! {
+                        permit = host_limit.acquire().fuse() => Some(permit),
+                        _ = self.control.wait_stopped().fuse() => None,
+                    };
+                    self.control.stop_waiting();
+                    match permit {
+                        Some(permit) => Some(permit),
+                        None => break,
+                    }
+                }
+                None => None,
+            };
+            let pair = match self.stack.pop() {
+                Some(pair) => pair,
+                None => break,
+            };
+            let pairs = self.coalesce(pair);
+            self.connection.request(&pairs);
+            let result = if pairs.len() > 1 {
+                self.req_ranges(pairs).await
+            } else {
+                self.req(pair).await
+            };
+            let failed = result.is_err();
+            match result {
+                // Fail the whole task when `Error::InnerError` is returned
                 Err(Error::InnerError(msg)) => {
                     tracing::error!("RangeRequestTask {}: InnerError: {}", self.id, msg);
-                    actix_rt::System::current().stop();
+                    return Err(Error::InnerError(msg));
+                }
+                // Request the pairs one by one from now on
+                Err(Error::InvalidByteRanges(msg)) => {
+                    tracing::debug!("RangeRequestTask {}: disable multi-range requests: {}", self.id, msg);
+                    self.multi_range.store(false, Ordering::Relaxed);
                 }
                 Err(err @ Error::Timeout) => {
                     tracing::debug!("RangeRequestTask timeout: {}", err); // Missing Timeout at runtime
+                    self.control.add_error();
+                }
+                // Avoid the route which fails, if there are other routes
+                Err(err) if self.is_failed_route(&err) => match self.pool.fail(self.route) {
+                    Some(route) => {
+                        tracing::debug!(
+                            "RangeRequestTask {}: route {} fails, switch to {}: {}",
+                            self.id,
+                            self.pool.name(self.route),
+                            self.pool.name(route),
+                            err
+                        );
+                        self.route = route;
+                    }
+                    None => {
+                        tracing::debug!("RangeRequestTask {}: error: {}", self.id, err);
+                        return Err(err);
+                    }
+                },
+                // The server is overloaded, leave the pairs to other tasks
+                Err(Error::Unsuccess(code)) if self.control.is_adaptive() && (code == 429 || code == 503) => {
+                    tracing::debug!("RangeRequestTask {}: status: {}", self.id, code);
+                    self.control.add_error();
+                    return Ok(());
                 }
                 // Return other response errors
                 Err(err) => {
@@ -359,13 +718,27 @@ impl RangeRequestTask {
                 }
                 _ => {}
             }
+            if failed {
+                self.connection.add_retry();
+                self.control.add_retry();
+            }
         }
         Ok(())
     }
 
+    /// Whether the error is caused by the route, e.g. the address can't be connected, or the proxy
+    /// refuses the request or can't reach the server
+    fn is_failed_route(&self, err: &Error) -> bool {
+        match err {
+            Error::RequestError(err) => err.is_connect(),
+            Error::Unsuccess(code) => self.pool.is_proxied() && [407, 502, 503].contains(code),
+            _ => false,
+        }
+    }
+
     async fn req(&mut self, pair: RangePair) -> Result<()> {
         let resp = request(
-            &self.client,
+            &self.pool.client(self.route),
             self.method.clone(),
             self.url.clone(),
             self.data.clone(),
@@ -378,6 +751,10 @@ impl RangeRequestTask {
             return Err(err);
         }
         let resp = resp.unwrap();
+        if let Err(err) = is_identity(&resp) {
+            self.stack.push(pair);
+            return Err(err);
+        }
 
         let RangePair { begin, end } = pair;
         let length = pair.length();
@@ -389,7 +766,11 @@ impl RangeRequestTask {
         // Set timeout for reading
         let tick = interval_stream(self.timeout).fuse();
 
-        pin_mut!(stream, tick);
+        // Don't wait for the next chunk of a stalled response when the task is paused or stopped
+        let control = self.control.clone();
+        let interrupted = control.wait_interrupted().fuse();
+
+        pin_mut!(stream, tick, interrupted);
         let mut fire = false;
         loop {
             select! {
@@ -414,6 +795,19 @@ impl RangeRequestTask {
                                 }
                                 offset += len as u64;
                                 count += len as u64;
+                                self.control.add_received(len as u64);
+                                self.connection.add_received(len as u64);
+                                self.control.throttle(len as u64).await;
+                                // The timeout is for reading, which restarts after a chunk
+                                fire = false;
+
+                                // Leave the rest of the pair when the task is paused or stopped
+                                if self.control.is_paused() || self.control.is_stopped() {
+                                    if offset <= end {
+                                        self.stack.push(RangePair::new(offset, end));
+                                    }
+                                    return Ok(());
+                                }
                             }
                             Err(err) => {
                                 let pr = RangePair::new(offset, end);
@@ -434,6 +828,12 @@ impl RangeRequestTask {
                         fire = true;
                     }
                 }
+                _ = interrupted => {
+                    if offset <= end {
+                        self.stack.push(RangePair::new(offset, end));
+                    }
+                    return Ok(());
+                }
             }
         }
 
@@ -446,4 +846,169 @@ impl RangeRequestTask {
             Ok(())
         }
     }
+
+    /// Request the pairs in one multi-range request, and route each part of the response to the
+    /// pairs
+    ///
+    /// The server can merge or reorder the ranges, so only the intersections of the parts and the
+    /// pairs are sent. The pairs which are not received are pushed back to the stack.
+    async fn req_ranges(&mut self, pairs: Vec<RangePair>) -> Result<()> {
+        let resp = request_ranges(
+            &self.pool.client(self.route),
+            self.method.clone(),
+            self.url.clone(),
+            self.data.clone(),
+            &pairs,
+        )
+        .await;
+
+        let resp = match resp.and_then(|resp| is_identity(&resp).map(|_| resp)) {
+            Ok(resp) => resp,
+            Err(err) => {
+                pairs.iter().for_each(|pair| self.stack.push(*pair));
+                return Err(err);
+            }
+        };
+
+        let header = |name: &str| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_owned())
+        };
+        let mut parser = None;
+        let mut single = None;
+        if resp.status().as_u16() == 206 {
+            if let Some(boundary) = header("content-type").as_deref().and_then(multipart_boundary) {
+                parser = Some(ByteRangesParser::new(&boundary));
+            } else if let Some(content_range) = header("content-range") {
+                // The server can merge the ranges into one range, which must cover all pairs
+                single = parse_content_range(&content_range)
+                    .ok()
+                    .filter(|range| pairs.iter().all(|p| range.begin <= p.begin && p.end <= range.end))
+                    .map(|range| range.begin);
+            }
+        }
+        if parser.is_none() && single.is_none() {
+            pairs.iter().for_each(|pair| self.stack.push(*pair));
+            return Err(Error::InvalidByteRanges(format!(
+                "Unexpected response: status: {}, content-type: {:?}",
+                resp.status(),
+                header("content-type")
+            )));
+        }
+
+        let mut remaining = pairs;
+        let stream = resp.bytes_stream().fuse();
+
+        // Set timeout for reading
+        let tick = interval_stream(self.timeout).fuse();
+
+        // Don't wait for the next chunk of a stalled response when the task is paused or stopped
+        let control = self.control.clone();
+        let interruption = control.wait_interrupted().fuse();
+
+        pin_mut!(stream, tick, interruption);
+        let mut fire = false;
+        let mut interrupted = false;
+        let result = loop {
+            select! {
+                item = stream.next() => {
+                    let chunk = match item {
+                        Some(Ok(chunk)) => chunk,
+                        Some(Err(err)) => break Err(err.into()),
+                        None => break Ok(()),
+                    };
+                    let parts = match (&mut parser, &mut single) {
+                        (Some(parser), _) => match parser.feed(&chunk) {
+                            Ok(parts) => parts,
+                            Err(err) => break Err(err),
+                        },
+                        (None, Some(offset)) => {
+                            let part = (*offset, chunk.clone());
+                            *offset += chunk.len() as u64;
+                            vec![part]
+                        }
+                        (None, None) => unreachable!(),
+                    };
+
+                    for (offset, bytes) in parts {
+                        if bytes.is_empty() {
+                            continue;
+                        }
+                        let part = RangePair::new(offset, offset + bytes.len() as u64 - 1);
+                        let intersections = take_intersections(&mut remaining, part);
+                        // The bytes out of the pairs are dropped
+                        let taken: u64 = intersections.iter().map(RangePair::length).sum();
+                        self.control.add_redownloaded(part.length() - taken);
+                        for pr in intersections {
+                            self.control.add_received(pr.length());
+                            self.connection.add_received(pr.length());
+                            let chunk = bytes.slice((pr.begin - offset) as usize..(pr.end - offset + 1) as usize);
+                            if let Err(err) = self.sender.send((pr, chunk)).await {
+                                remaining.iter().for_each(|pair| self.stack.push(*pair));
+                                return Err(Error::InnerError(format!(
+                                    "Error at `http::RangeRequestTask`: Sender error: {:?}",
+                                    err
+                                )));
+                            }
+                        }
+                    }
+
+                    self.control.throttle(chunk.len() as u64).await;
+                    // The timeout is for reading, which restarts after a chunk
+                    fire = false;
+
+                    // Leave the rest of the pairs when the task is paused or stopped
+                    if self.control.is_paused() || self.control.is_stopped() {
+                        interrupted = true;
+                        break Ok(());
+                    }
+                }
+                _ = tick.next() => {
+                    if fire {
+                        break Err(Error::Timeout);
+                    } else {
+                        fire = true;
+                    }
+                }
+                _ = interruption => {
+                    interrupted = true;
+                    break Ok(());
+                }
+            }
+        };
+
+        // Push back the pairs which are not received
+        let uncompleted = !remaining.is_empty();
+        remaining.iter().for_each(|pair| self.stack.push(*pair));
+        match result {
+            Err(err) => Err(err),
+            Ok(_) if uncompleted && !interrupted => Err(Error::UncompletedRead),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+/// Remove the intersections of the part from the pairs, and return the intersections
+fn take_intersections(pairs: &mut Vec<RangePair>, part: RangePair) -> Vec<RangePair> {
+    let mut intersections = vec![];
+    let mut rest = vec![];
+    for pair in pairs.drain(..) {
+        if pair.end < part.begin || part.end < pair.begin {
+            rest.push(pair);
+            continue;
+        }
+        let begin = std::cmp::max(pair.begin, part.begin);
+        let end = std::cmp::min(pair.end, part.end);
+        intersections.push(RangePair::new(begin, end));
+        if pair.begin < begin {
+            rest.push(RangePair::new(pair.begin, begin - 1));
+        }
+        if end < pair.end {
+            rest.push(RangePair::new(end + 1, pair.end));
+        }
+    }
+    *pairs = rest;
+    intersections
 }
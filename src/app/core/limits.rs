@@ -0,0 +1,180 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    arguments::options::DownloadOptions,
+    common::errors::{Error, Result},
+};
+
+/// The limits of a download, which abort it with an error
+///
+/// The received data and records are kept, so an aborted download can be resumed with other limits.
+#[derive(Debug, Clone)]
+pub struct DownloadLimits {
+    max_filesize: Option<u64>,
+    speed_limit: Option<u64>,
+    speed_time: Duration,
+    max_time: Option<u64>,
+    deadline: Option<Instant>,
+    // The time and the received bytes when the speed falls below the limit
+    low_since: Option<(Instant, u64)>,
+    // The received bytes at the last check
+    last: Option<(Instant, u64)>,
+}
+
+impl DownloadLimits {
+    /// The deadline of `--max-time` starts now
+    pub fn new(options: &DownloadOptions) -> DownloadLimits {
+        DownloadLimits {
+            max_filesize: options.max_filesize(),
+            speed_limit: options.speed_limit(),
+            speed_time: Duration::from_secs(options.speed_time()),
+            max_time: options.max_time(),
+            deadline: options
+                .max_time()
+                .map(|max_time| Instant::now() + Duration::from_secs(max_time)),
+            low_since: None,
+            last: None,
+        }
+    }
+
+    /// Use the deadline of the whole task, which is shared by its retries
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> DownloadLimits {
+        if deadline.is_some() {
+            self.deadline = deadline;
+        }
+        self
+    }
+
+    /// Reject the file which is larger than `--max-filesize`
+    pub fn check_filesize(&self, size: u64) -> Result<()> {
+        match self.max_filesize {
+            Some(max) if size > max => Err(Error::FileTooLarge(size, max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail after the deadline of `--max-time`
+    pub fn check_deadline(&self) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(Error::MaxTimeExceeded(self.max_time.unwrap_or_default()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run the future, e.g. probing the resource, until the deadline
+    pub async fn within<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        self.check_deadline()?;
+        match self.deadline {
+            Some(deadline) => {
+                let remains = deadline.saturating_duration_since(Instant::now());
+                tokio::time::timeout(remains, future)
+                    .await
+                    .unwrap_or_else(|_| Err(Error::MaxTimeExceeded(self.max_time.unwrap_or_default())))
+            }
+            None => future.await,
+        }
+    }
+
+    /// Check the limits with the size of the file and the bytes which are received from the network,
+    /// which is called periodically while the data is received
+    pub fn check(&mut self, size: u64, received: u64, paused: bool) -> Result<()> {
+        self.check_filesize(size)?;
+        self.check_deadline()?;
+        self.check_speed(Instant::now(), received, paused)
+    }
+
+    /// The speed is low once it falls below the limit between two checks, until the average speed
+    /// since then reaches the limit. The time of pauses is not counted.
+    fn check_speed(&mut self, now: Instant, received: u64, paused: bool) -> Result<()> {
+        let limit = match self.speed_limit {
+            Some(limit) if !paused => limit,
+            _ => {
+                self.low_since = None;
+                self.last = None;
+                return Ok(());
+            }
+        };
+        let rate = |since: Instant, from: u64| {
+            let elapsed = now.duration_since(since).as_secs_f64();
+            (received.saturating_sub(from)) as f64 / elapsed.max(f64::EPSILON)
+        };
+
+        if let Some((since, from)) = self.low_since {
+            if rate(since, from) >= limit as f64 {
+                self.low_since = None;
+            } else if now.duration_since(since) >= self.speed_time {
+                return Err(Error::TooSlow(limit, self.speed_time.as_secs()));
+            }
+        } else if let Some((since, from)) = self.last {
+            if rate(since, from) < limit as f64 {
+                self.low_since = Some((since, from));
+            }
+        }
+        self.last = Some((now, received));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::DownloadLimits;
+    use crate::common::errors::Error;
+
+    fn limits() -> DownloadLimits {
+        DownloadLimits {
+            max_filesize: Some(1000),
+            speed_limit: Some(100),
+            speed_time: Duration::from_secs(5),
+            max_time: None,
+            deadline: None,
+            low_since: None,
+            last: None,
+        }
+    }
+
+    #[test]
+    fn test_check_filesize() {
+        let limits = limits();
+        assert!(limits.check_filesize(1000).is_ok());
+        assert!(matches!(
+            limits.check_filesize(1001),
+            Err(Error::FileTooLarge(1001, 1000))
+        ));
+    }
+
+    #[test]
+    fn test_check_speed() {
+        let begin = Instant::now();
+        let at = |secs: u64| begin + Duration::from_secs(secs);
+
+        // A short drop of the speed is recovered
+        let mut limits = limits();
+        assert!(limits.check_speed(at(0), 0, false).is_ok());
+        assert!(limits.check_speed(at(1), 200, false).is_ok());
+        assert!(limits.check_speed(at(2), 210, false).is_ok());
+        assert!(limits.check_speed(at(3), 500, false).is_ok());
+        assert!(limits.low_since.is_none());
+
+        // The speed stays below the limit
+        for secs in 4..8 {
+            assert!(limits.check_speed(at(secs), 500 + secs * 10, false).is_ok());
+        }
+        assert!(matches!(
+            limits.check_speed(at(8), 580, false),
+            Err(Error::TooSlow(100, 5))
+        ));
+
+        // Pauses are not counted
+        let mut limits = self::limits();
+        for secs in 0..10 {
+            assert!(limits.check_speed(at(secs), 0, true).is_ok());
+        }
+    }
+}
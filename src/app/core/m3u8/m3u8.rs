@@ -1,89 +1,122 @@
 use std::{
-    cell::Cell,
+    cmp::Reverse,
     path::PathBuf,
-    rc::Rc,
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use futures::{
-    channel::mpsc::{channel, Sender},
-    pin_mut, select, SinkExt, StreamExt,
+    channel::mpsc::{channel, unbounded, Sender},
+    pin_mut, select, FutureExt, SinkExt, StreamExt,
 };
+use tokio::task::JoinSet;
 
 use crate::{
     app::{
-        core::m3u8::common::{get_m3u8, M3u8Segment, SharedM3u8SegmentList},
+        core::{
+            concurrency::ConcurrencyControl,
+            limits::DownloadLimits,
+            m3u8::common::{get_m3u8, M3u8Segment, SharedM3u8SegmentList},
+        },
         receive::m3u8_receiver::M3u8Receiver,
         record::{bytearray_recorder::ByteArrayRecorder, common::RECORDER_FILE_SUFFIX},
+        show::common::ProgressFormat,
+        status::summary::Summary,
     },
+    arguments::options::DownloadOptions,
     common::{
         bytes::bytes_type::Bytes,
         crypto::decrypt_aes128,
         errors::{Error, Result},
         net::{
-            net::{build_http_client, request},
+            net::{build_http_client, request, HttpClientOptions},
             HttpClient, Method, Url,
         },
         time::interval_stream,
     },
-    features::{args::Args, running::Runnable, stack::StackLike},
+    features::{
+        running::{block_on, Runnable},
+        stack::StackLike,
+    },
 };
 
 /// M3u8 task handler
-pub struct M3u8Handler<'a> {
+pub struct M3u8Handler {
     output: PathBuf,
     method: Method,
     url: Url,
-    data: Option<&'a str>,
+    data: Option<String>,
     concurrency: u64,
     timeout: Duration,
     client: HttpClient,
+    progress: ProgressFormat,
+    control: ConcurrencyControl,
+    limits: DownloadLimits,
 }
 
-impl<'a> M3u8Handler<'a> {
-    pub fn new(args: &impl Args) -> Result<M3u8Handler<'_>> {
-        let headers = args.headers();
-        let timeout = args.timeout();
-        let dns_timeout = args.dns_timeout();
-        let keep_alive = args.keep_alive();
-        let skip_verify_tls_cert = args.skip_verify_tls_cert();
-        let proxy = args.proxy();
+impl M3u8Handler {
+    pub fn new(options: &DownloadOptions) -> Result<M3u8Handler> {
+        options.check()?;
+        let timeout = options.timeout();
+        let client = build_http_client(&HttpClientOptions::from_options(options))?;
 
-        let client = build_http_client(&headers, timeout, dns_timeout, keep_alive, skip_verify_tls_cert, proxy)?;
+        let control = ConcurrencyControl::new(options.concurrency(), false);
+        control.set_limit(options.limit_rate().unwrap_or(0));
 
         tracing::debug!("M3u8Handler::new");
 
         Ok(M3u8Handler {
-            output: args.output(),
-            method: args.method(),
-            url: args.url(),
-            data: args.data(),
-            concurrency: args.concurrency(),
+            output: options.output(),
+            method: options.method(),
+            url: options.url(),
+            data: options.data().map(|v| v.to_owned()),
+            concurrency: options.concurrency(),
             timeout,
             client,
+            progress: options.progress(),
+            control,
+            limits: DownloadLimits::new(options),
         })
     }
 
-    async fn start(self) -> Result<()> {
+    /// Use the control which is shared with other threads, to pause, resume or stop the task
+    pub fn with_control(mut self, control: ConcurrencyControl) -> M3u8Handler {
+        self.control = control;
+        self
+    }
+
+    /// Use the deadline of `--max-time` which starts before the first try of the task
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> M3u8Handler {
+        self.limits = self.limits.with_deadline(deadline);
+        self
+    }
+
+    /// Run the task on the current Tokio runtime. Requests are spawned as tasks, which are aborted
+    /// when the future finishes or is dropped
+    pub async fn start(self) -> Result<Option<Summary>> {
         tracing::debug!("M3u8Handler::start");
 
         // 0. Check whether task is completed
         tracing::debug!("M3u8Handler: check whether task is completed");
         let mut bytearrayrecorder = ByteArrayRecorder::new(&*(self.output.to_string_lossy() + RECORDER_FILE_SUFFIX))?;
         if self.output.exists() && !bytearrayrecorder.exists() {
-            return Ok(());
+            return Ok(None);
         }
 
         // 1. Get m3u8 info
         tracing::debug!("M3u8Handler: get m3u8");
-        let mut ls = get_m3u8(
-            &self.client,
-            self.method.clone(),
-            self.url.clone(),
-            self.data.map(|v| v.to_string()),
-        )
-        .await?;
+        let mut ls = self
+            .limits
+            .within(get_m3u8(
+                &self.client,
+                self.method.clone(),
+                self.url.clone(),
+                self.data.clone(),
+            ))
+            .await?;
         ls.reverse();
 
         // 2. Check recorder status
@@ -104,7 +137,7 @@ impl<'a> M3u8Handler<'a> {
 
         // Use atomic u64 to control the order of sending segment content
         let index = ls.last().unwrap().index;
-        let sharedindex = Rc::new(Cell::new(index));
+        let sharedindex = Arc::new(AtomicU64::new(index));
         let stack = SharedM3u8SegmentList::new(ls);
         tracing::debug!("M3u8Handler: segments: {}", stack.len());
 
@@ -113,21 +146,56 @@ impl<'a> M3u8Handler<'a> {
         let runtime_error: Arc<Mutex<Option<Error>>> = Arc::new(Mutex::new(None));
 
         // 4. Spawn request task
-        let concurrency = std::cmp::min(stack.len() as u64, self.concurrency);
-        for i in 1..concurrency + 1 {
-            let mut task = RequestTask::new(
-                self.client.clone(),
-                stack.clone(),
-                sender.clone(),
-                i,
-                sharedindex.clone(),
-                self.timeout,
-            );
-            let runtime_error_clone = runtime_error.clone();
-            actix_rt::spawn(async move {
-                if let Err(err) = task.start().await {
-                    if runtime_error_clone.lock().unwrap().is_none() {
-                        *runtime_error_clone.lock().unwrap() = Some(err);
+        // Spawn tasks until the target concurrency, and spawn more when the target is raised
+        let control = self.control.clone();
+        let mut tasks = JoinSet::new();
+        {
+            let (client, timeout) = (self.client.clone(), self.timeout);
+            let (control, sender, runtime_error) = (control.clone(), sender.clone(), runtime_error.clone());
+            // Tasks wake up the dispatcher when they exit
+            let (waker, mut wakeup) = unbounded::<()>();
+            tasks.spawn(async move {
+                // Request tasks are aborted with the dispatcher
+                let mut request_tasks = JoinSet::new();
+                let mut id = 0;
+                loop {
+                    while request_tasks.try_join_next().is_some() {}
+                    if runtime_error.lock().unwrap().is_some()
+                        || ((stack.len() == 0 || control.is_stopped()) && control.active() == 0)
+                    {
+                        break;
+                    }
+
+                    while !control.is_stopped()
+                        && control.active() < control.target()
+                        && control.active() < stack.len() as u64
+                    {
+                        id += 1;
+                        let mut task = RequestTask::new(
+                            client.clone(),
+                            stack.clone(),
+                            sender.clone(),
+                            id,
+                            sharedindex.clone(),
+                            timeout,
+                            control.clone(),
+                        );
+                        control.enter();
+                        let (control, runtime_error, waker) = (control.clone(), runtime_error.clone(), waker.clone());
+                        request_tasks.spawn(async move {
+                            if let Err(err) = task.start().await {
+                                if runtime_error.lock().unwrap().is_none() {
+                                    *runtime_error.lock().unwrap() = Some(err);
+                                }
+                            }
+                            control.leave();
+                            let _ = waker.unbounded_send(());
+                        });
+                    }
+
+                    select! {
+                        _ = wakeup.next() => {},
+                        _ = tokio::time::sleep(Duration::from_secs(1)).fuse() => {},
                     }
                 }
             });
@@ -136,25 +204,31 @@ impl<'a> M3u8Handler<'a> {
 
         // 5. Create receiver
         tracing::debug!("M3u8Handler: create receiver");
-        let mut m3u8receiver = M3u8Receiver::new(&self.output)?;
+        let mut m3u8receiver =
+            M3u8Receiver::new(&self.output, control, self.progress)?.with_limits(self.limits.clone());
         m3u8receiver.start(receiver).await?;
 
         if let Some(err) = runtime_error.lock().unwrap().take() {
             return Err(err);
         }
+        if self.control.is_stopped() {
+            return Err(Error::Cancelled);
+        }
 
         // 6. Fixup output file
 
         // 7. Task succeeds. Remove `ByteArrayRecorder` file
         bytearrayrecorder.remove().unwrap_or(()); // Missing error
-        Ok(())
+
+        let summary = m3u8receiver.summary(self.url.as_str());
+        m3u8receiver.show_summary(&summary)?;
+        Ok(Some(summary))
     }
 }
 
-impl<'a> Runnable for M3u8Handler<'a> {
-    fn run(self) -> Result<()> {
-        let sys = actix_rt::System::new();
-        sys.block_on(self.start())
+impl Runnable for M3u8Handler {
+    fn run(self) -> Result<Option<Summary>> {
+        block_on(self.start())?
     }
 }
 
@@ -164,8 +238,9 @@ struct RequestTask {
     stack: SharedM3u8SegmentList,
     sender: Sender<(u64, Bytes)>,
     id: u64,
-    shared_index: Rc<Cell<u64>>,
+    shared_index: Arc<AtomicU64>,
     timeout: Duration,
+    control: ConcurrencyControl,
 }
 
 impl RequestTask {
@@ -174,8 +249,9 @@ impl RequestTask {
         stack: SharedM3u8SegmentList,
         sender: Sender<(u64, Bytes)>,
         id: u64,
-        sharedindex: Rc<Cell<u64>>,
+        sharedindex: Arc<AtomicU64>,
         timeout: Duration,
+        control: ConcurrencyControl,
     ) -> RequestTask {
         RequestTask {
             client,
@@ -184,21 +260,32 @@ impl RequestTask {
             id,
             shared_index: sharedindex,
             timeout,
+            control,
         }
     }
 
     async fn start(&mut self) -> Result<()> {
         tracing::debug!("Fire RequestTask: {}", self.id);
-        while let Some(segment) = self.stack.pop() {
+        loop {
+            // Hold on while the task is paused, and exit when it is stopped or there are too many tasks
+            self.control.wait_resumed().await;
+            if self.control.is_stopped() || self.control.is_over() {
+                break;
+            }
+            let segment = match self.stack.pop() {
+                Some(segment) => segment,
+                None => break,
+            };
             loop {
                 match self.req(segment.clone()).await {
-                    // Exit whole process when `Error::InnerError` is returned
+                    // Fail the whole task when `Error::InnerError` is returned
                     Err(Error::InnerError(msg)) => {
                         tracing::error!("RequestTask {}: InnerError: {}", self.id, msg);
-                        actix_rt::System::current().stop();
+                        return Err(Error::InnerError(msg));
                     }
                     Err(err @ Error::Timeout) => {
                         tracing::debug!("RequestTask timeout: {:?}", err); // Missing Timeout at runtime
+                        self.control.add_retry();
                     }
                     Err(err) => {
                         tracing::debug!("RequestTask {}: error: {:?}", self.id, err);
@@ -231,7 +318,11 @@ impl RequestTask {
         // Set timeout for reading
         let tick = interval_stream(self.timeout).fuse();
 
-        pin_mut!(stream, tick);
+        // Don't wait for the next chunk of a stalled response when the task is paused or stopped
+        let control = self.control.clone();
+        let interrupted = control.wait_interrupted().fuse();
+
+        pin_mut!(stream, tick, interrupted);
         let mut fire = false;
         loop {
             select! {
@@ -239,7 +330,12 @@ impl RequestTask {
                     if let Some(item) = item {
                         match item {
                             Ok(chunk) => {
+                                let len = chunk.len() as u64;
                                 buf.extend(chunk);
+                                self.control.add_received(len);
+                                self.control.throttle(len).await;
+                                // The timeout is for reading, which restarts after a chunk
+                                fire = false;
                             }
                             Err(err) => return Err(err.into()),
                         }
@@ -249,11 +345,22 @@ impl RequestTask {
                 }
                 _ = tick.next() => {
                     if fire {
+                        // The segment is requested again
+                        self.control.add_redownloaded(buf.len() as u64);
                         return Err(Error::Timeout);
                     } else {
                         fire = true;
                     }
                 }
+                _ = interrupted => {
+                    // The connection is closed while the task is paused, and the segment is
+                    // requested again when it is resumed
+                    self.control.add_redownloaded(buf.len() as u64);
+                    self.stack.push(segment);
+                    // Segments are sent in order, so the first one is popped first
+                    self.stack.sort_by_key(|segment| Reverse(segment.index));
+                    return Ok(());
+                }
             }
         }
 
@@ -265,17 +372,21 @@ impl RequestTask {
         };
 
         loop {
-            if self.shared_index.get() == index {
+            // The segments before it will never be sent
+            if self.control.is_stopped() {
+                return Ok(());
+            }
+            if self.shared_index.load(Ordering::SeqCst) == index {
                 if let Err(err) = self.sender.send((index, Bytes::from(de))).await {
                     return Err(Error::InnerError(format!(
                         "Error at `http::RequestTask`: Sender error: {:?}",
                         err
                     )));
                 }
-                self.shared_index.set(index + 1);
+                self.shared_index.store(index + 1, Ordering::SeqCst);
                 return Ok(());
             } else {
-                actix_rt::time::sleep(Duration::from_millis(500)).await;
+                tokio::time::sleep(Duration::from_millis(500)).await;
             }
         }
     }
@@ -1,3 +1,9 @@
+pub mod batch;
 pub mod bt;
+pub mod concurrency;
+pub mod downloader;
 pub mod http;
+pub mod limits;
 pub mod m3u8;
+pub mod retry;
+pub mod signals;
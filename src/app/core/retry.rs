@@ -0,0 +1,206 @@
+use std::{
+    error::Error as StdError,
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{arguments::options::DownloadOptions, common::errors::Error};
+
+/// The policy of retrying a failed task
+///
+/// The wait grows exponentially from `--retry-wait` up to `--retry-max-wait`, and half of it is
+/// random, so that tasks don't retry at the same time. Errors which fail again, e.g. 4xx responses,
+/// inconsistent records and invalid certificates, are not retried, and no retry starts after the
+/// deadline of `--max-time`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    retries: u64,
+    wait: Duration,
+    max_wait: Duration,
+    statuses: Vec<u16>,
+    deadline: Option<Instant>,
+}
+
+impl RetryPolicy {
+    /// The deadline of `--max-time` starts now
+    pub fn new(options: &DownloadOptions) -> RetryPolicy {
+        RetryPolicy {
+            retries: options.retries(),
+            wait: Duration::from_secs(options.retry_wait()),
+            max_wait: Duration::from_secs(options.retry_max_wait()),
+            statuses: options.retry_statuses(),
+            deadline: options
+                .max_time()
+                .map(|max_time| Instant::now() + Duration::from_secs(max_time)),
+        }
+    }
+
+    /// The maximum times of retrying
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// The deadline of the whole task
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Whether the task can succeed if it is retried after the error
+    ///
+    /// The errors are classified by themselves rather than by their exit codes, so a fault of the
+    /// server is retried even if its exit code is shared with errors of the arguments.
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        match err {
+            Error::Unsuccess(code) => self.statuses.contains(code),
+            Error::RequestError(err) => match err.status() {
+                Some(status) => self.statuses.contains(&status.as_u16()),
+                None => !is_certificate_error(err),
+            },
+
+            // Faults of the network or the server, which can succeed later
+            Error::UnexpectedEof
+            | Error::Timeout
+            | Error::NetError(_)
+            | Error::UncompletedRead
+            | Error::DnsError(_)
+            | Error::InvalidResponseUrl(_)
+            | Error::InvalidByteRanges(_)
+            | Error::M3U8ParseFail
+            | Error::NoContentLength
+            | Error::InvaildHeader(_)
+            | Error::Redirect(_)
+            | Error::NoLocation(_)
+            | Error::TooManyRedirects(_)
+            | Error::BitTorrentError(_)
+            | Error::Bug(_)
+            | Error::InnerError(_) => true,
+
+            // Errors of the arguments, the disk, the records and the limits, which fail again
+            Error::Cancelled
+            | Error::EncodedRangeResponse(_)
+            | Error::InvalidPath(_)
+            | Error::InvaildUri(_)
+            | Error::InvalidHeader(_)
+            | Error::NoFilename
+            | Error::NotFoundDirectory
+            | Error::FileExists
+            | Error::PathIsDirectory
+            | Error::IsNotNumber(_)
+            | Error::Io(_)
+            | Error::UnsupportedTask(_)
+            | Error::InvalidLogFile(_)
+            | Error::NoTerminal
+            | Error::InvalidArgument(_)
+            | Error::InvalidConfig(_)
+            | Error::UnsupportedMethod(_)
+            | Error::HeaderParseError(_)
+            | Error::UrlParseError(_)
+            | Error::InvalidAddress(_)
+            | Error::InvalidProxyUser(_)
+            | Error::PacError(_)
+            | Error::ContentLengthIsNotConsistent
+            | Error::PartsAreNotConsistent
+            | Error::AES128DecryptFail(_)
+            | Error::FileTooLarge(..)
+            | Error::TooSlow(..)
+            | Error::MaxTimeExceeded(_) => false,
+        }
+    }
+
+    /// The wait before the retry after the error, where `attempt` starts from 1, or `None` if the
+    /// task is not retried
+    pub fn retry(&self, attempt: u64, err: &Error) -> Option<Duration> {
+        if attempt > self.retries || !self.is_retryable(err) {
+            return None;
+        }
+        let wait = self.backoff(attempt);
+        match self.deadline {
+            Some(deadline) if Instant::now() + wait >= deadline => None,
+            _ => Some(wait),
+        }
+    }
+
+    /// The exponential wait with a random half
+    fn backoff(&self, attempt: u64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31) as u32;
+        let wait = self.wait.saturating_mul(2u32.pow(exponent)).min(self.max_wait);
+        wait.mul_f64(0.5 + rand::random::<f64>() / 2.0)
+    }
+}
+
+/// Whether the server's TLS certificate is refused, which fails again
+fn is_certificate_error(err: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if matches!(
+            err.downcast_ref::<rustls::Error>(),
+            Some(rustls::Error::InvalidCertificate(_) | rustls::Error::NoCertificatesPresented)
+        ) {
+            return true;
+        }
+        // The source of an io error skips the error which it wraps, e.g. the error of TLS
+        source = match err.downcast_ref::<io::Error>().and_then(|err| err.get_ref()) {
+            Some(inner) => Some(inner as &(dyn StdError + 'static)),
+            None => err.source(),
+        };
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        time::{Duration, Instant},
+    };
+
+    use super::{is_certificate_error, RetryPolicy};
+    use crate::common::errors::Error;
+
+    fn policy(deadline: Option<Instant>) -> RetryPolicy {
+        RetryPolicy {
+            retries: 5,
+            wait: Duration::from_secs(1),
+            max_wait: Duration::from_secs(10),
+            statuses: vec![429, 503],
+            deadline,
+        }
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = policy(None);
+        assert!(policy.retry(1, &Error::Unsuccess(503)).is_some());
+        assert!(policy.retry(1, &Error::Unsuccess(404)).is_none());
+        assert!(policy.retry(1, &Error::ContentLengthIsNotConsistent).is_none());
+        assert!(policy.retry(1, &Error::NoFilename).is_none());
+        assert!(policy.retry(1, &Error::Timeout).is_some());
+        assert!(policy.retry(6, &Error::Timeout).is_none());
+
+        for attempt in 1..=5 {
+            let wait = policy.backoff(attempt);
+            let full = Duration::from_secs(1 << (attempt - 1)).min(Duration::from_secs(10));
+            assert!(wait >= full / 2 && wait <= full);
+        }
+
+        let policy = self::policy(Some(Instant::now() + Duration::from_millis(100)));
+        assert!(policy.retry(1, &Error::Timeout).is_none());
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let policy = policy(None);
+        // Faults of the server are retried, even if they are reported like errors of the arguments
+        assert!(policy.is_retryable(&Error::InvaildHeader("content-range: bytes 0-1/x".to_owned())));
+        assert!(policy.is_retryable(&Error::InvalidResponseUrl("http://[::1".to_owned())));
+        assert!(!policy.is_retryable(&Error::InvalidHeader("x: \n".to_owned())));
+        assert!(!policy.is_retryable(&Error::Cancelled));
+
+        // The error of TLS is wrapped by io errors
+        let refused = rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer);
+        let err = io::Error::other(io::Error::new(io::ErrorKind::InvalidData, refused));
+        assert!(is_certificate_error(&err));
+        let err = io::Error::other(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert!(!is_certificate_error(&err));
+    }
+}
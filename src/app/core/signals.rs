@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use crate::{app::core::concurrency::ConcurrencyControl, common::errors::Result};
+
+/// The signal which stops the tasks, 0 if there is none
+static STOP_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Stop the tasks on the first SIGINT or SIGTERM, and exit at once on the second one. Pause the
+/// tasks on SIGUSR1, and resume them on SIGUSR2 or SIGCONT
+///
+/// Stopped tasks write their received data and records to the disk before they exit, so they can
+/// be resumed. Paused range requests give their ranges back and close their connections.
+#[cfg(unix)]
+pub fn handle_signals(controls: Vec<ConcurrencyControl>) -> Result<()> {
+    use signal_hook::{
+        consts::{SIGCONT, SIGINT, SIGTERM, SIGUSR1, SIGUSR2},
+        iterator::Signals,
+    };
+
+    use crate::common::terminal::restore_terminal;
+
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGUSR1, SIGUSR2, SIGCONT])?;
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGUSR1 => {
+                    tracing::debug!("Signal {}: pause tasks", signal);
+                    controls.iter().for_each(|control| control.pause());
+                }
+                SIGUSR2 | SIGCONT => {
+                    tracing::debug!("Signal {}: resume tasks", signal);
+                    controls.iter().for_each(|control| control.resume());
+                }
+                _ => {
+                    if STOP_SIGNAL.swap(signal, Ordering::SeqCst) != 0 {
+                        restore_terminal();
+                        std::process::exit(128 + signal);
+                    }
+                    tracing::debug!("Signal {}: stop tasks", signal);
+                    controls.iter().for_each(|control| control.stop());
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Signals are not handled on this platform
+#[cfg(not(unix))]
+pub fn handle_signals(_controls: Vec<ConcurrencyControl>) -> Result<()> {
+    Ok(())
+}
+
+/// The exit code of the process which is stopped by a signal, 128 + the signal number like shells
+pub fn signal_exit_code() -> Option<i32> {
+    match STOP_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(128 + signal),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use signal_hook::consts::{SIGCONT, SIGTERM, SIGUSR1, SIGUSR2};
+    use tokio::time::timeout;
+
+    use super::{handle_signals, signal_exit_code};
+    use crate::{app::core::concurrency::ConcurrencyControl, features::running::block_on};
+
+    #[test]
+    fn test_signals_control_tasks() {
+        let control = ConcurrencyControl::new(1, false);
+        handle_signals(vec![control.clone()]).unwrap();
+
+        // Signals are handled by another thread, so the transitions are waited for
+        let wait = Duration::from_secs(5);
+        let raise = |signal| unsafe {
+            libc::raise(signal);
+        };
+        block_on(async {
+            raise(SIGUSR1);
+            timeout(wait, control.wait_interrupted()).await.unwrap();
+            assert!(control.is_paused() && !control.is_stopped());
+
+            raise(SIGUSR2);
+            timeout(wait, control.wait_resumed()).await.unwrap();
+            assert!(!control.is_paused());
+
+            raise(SIGUSR1);
+            timeout(wait, control.wait_interrupted()).await.unwrap();
+            raise(SIGCONT);
+            timeout(wait, control.wait_resumed()).await.unwrap();
+            assert!(!control.is_paused());
+
+            // Only one stop signal is raised, the second one exits the process
+            assert_eq!(signal_exit_code(), None);
+            raise(SIGTERM);
+            timeout(wait, control.wait_stopped()).await.unwrap();
+            assert!(control.is_stopped());
+            assert_eq!(signal_exit_code(), Some(128 + SIGTERM));
+        })
+        .unwrap();
+    }
+}
@@ -1,38 +1,73 @@
 use std::{io::SeekFrom, path::Path, time::Duration};
 
-use futures::{channel::mpsc::Receiver, pin_mut, select, StreamExt};
+use futures::{
+    channel::mpsc::{unbounded, Receiver},
+    pin_mut, select, StreamExt,
+};
 
 use crate::{
     app::{
+        core::{concurrency::ConcurrencyControl, limits::DownloadLimits},
         record::{common::RECORDER_FILE_SUFFIX, range_recorder::RangeRecorder},
-        show::http_show::HttpShower,
-        status::rate_status::RateStatus,
+        show::{common::ProgressFormat, http_show::HttpShower},
+        status::{block_map::BlockMap, connection_status::ConnectionStatus, rate_status::RateStatus, summary::Summary},
+    },
+    common::{
+        bytes::bytes_type::Bytes, errors::Result, file::File, range::RangePair, size::HumanReadable,
+        terminal::KeyReader, time::interval_stream,
     },
-    common::{bytes::bytes_type::Bytes, errors::Result, file::File, range::RangePair, time::interval_stream},
 };
 
+/// The lowest speed limit which is set by the keys
+const MIN_LIMIT: u64 = 1024;
+
 pub struct HttpReceiver {
     output: File,
     rangerecorder: Option<RangeRecorder>,
     ratestatus: RateStatus,
     shower: HttpShower,
+    control: ConcurrencyControl,
+    limits: Option<DownloadLimits>,
+    // The status of connections and the map of downloaded blocks for the verbose mode
+    connections: ConnectionStatus,
+    blockmap: Option<BlockMap>,
+    verbose: bool,
+    // Whether the keys of the terminal control the task
+    interactive: bool,
+    // Whether the task is paused by the keys or signals, which is shown once it changes
+    paused: bool,
     // Total content length of the uri
     total: u64,
 }
 
 impl HttpReceiver {
-    pub fn new<P: AsRef<Path>>(output: P, direct: bool, content_length: u64) -> Result<HttpReceiver> {
+    pub fn new<P: AsRef<Path>>(
+        output: P,
+        direct: bool,
+        content_length: u64,
+        control: ConcurrencyControl,
+        connections: ConnectionStatus,
+        progress: ProgressFormat,
+        verbose: bool,
+    ) -> Result<HttpReceiver> {
         let mut outputfile = File::new(&output, true)?;
         outputfile.open()?;
 
-        let (rangerecorder, total, completed) = if direct {
-            (None, content_length, 0)
+        let (rangerecorder, total, completed, blockmap) = if direct {
+            (None, content_length, 0, None)
         } else {
             let mut rangerecorder = RangeRecorder::new(&*(output.as_ref().to_string_lossy() + RECORDER_FILE_SUFFIX))?;
             rangerecorder.open()?;
             let total = rangerecorder.total()?;
             let completed = rangerecorder.count()?;
-            (Some(rangerecorder), total, completed)
+            let blockmap = if verbose {
+                let mut blockmap = BlockMap::new(total);
+                rangerecorder.pairs()?.into_iter().for_each(|pair| blockmap.add(pair));
+                Some(blockmap)
+            } else {
+                None
+            };
+            (Some(rangerecorder), total, completed, blockmap)
         };
 
         let mut ratestatus = RateStatus::new();
@@ -42,36 +77,71 @@ impl HttpReceiver {
             output: outputfile,
             rangerecorder,
             ratestatus,
-            shower: HttpShower::new(),
+            shower: HttpShower::new(progress),
+            control,
+            limits: None,
+            connections,
+            blockmap,
+            verbose,
+            interactive: progress == ProgressFormat::Bar,
+            paused: false,
             // receiver,
             total,
         })
     }
 
-    fn show_infos(&mut self) -> Result<()> {
-        if self.rangerecorder.is_none() {
-            self.shower.print_msg("Server doesn't support range request.")?;
+    /// Abort the download when it exceeds the limits
+    pub fn with_limits(mut self, limits: DownloadLimits) -> HttpReceiver {
+        self.limits = Some(limits);
+        self
+    }
+
+    fn check_limits(&mut self) -> Result<()> {
+        let (size, received, paused) = (
+            self.ratestatus.total(),
+            self.control.received(),
+            self.control.is_paused(),
+        );
+        match self.limits {
+            Some(ref mut limits) => limits.check(size, received, paused),
+            None => Ok(()),
         }
+    }
 
+    fn show_infos(&mut self) -> Result<()> {
         let file_name = &self.output.file_name().unwrap_or("[No Name]");
         let total = self.total;
-        self.shower.print_file(file_name)?;
-        self.shower.print_total(total)?;
+        self.shower
+            .print_probe(file_name, total, self.rangerecorder.is_some())?;
         // self.shower.print_concurrency(concurrency)?;
         self.show_status()?;
         Ok(())
     }
 
     fn show_status(&mut self) -> Result<()> {
+        if self.control.is_paused() != self.paused {
+            self.paused = !self.paused;
+            let msg = match (self.paused, self.interactive) {
+                (true, true) => "Paused, press p to resume",
+                (true, false) => "Paused",
+                (false, _) => "Resumed",
+            };
+            self.shower.print_msg(msg)?;
+        }
+
         let total = self.total;
         let completed = self.ratestatus.total();
         let rate = self.ratestatus.rate();
 
+        // The smoothed rate is steadier than the rate of the tick for estimating the remaining time
+        self.ratestatus.clean();
+        let smoothed = self.ratestatus.smoothed_rate();
+
         let eta = if self.rangerecorder.is_some() || self.total != 0 {
             let remains = total - completed;
             // rate > 1.0 for overflow
-            if remains > 0 && rate > 1.0 {
-                let eta = (remains as f64 / rate) as u64;
+            if remains > 0 && smoothed > 1.0 {
+                let eta = (remains as f64 / smoothed) as u64;
                 // eta is large than 99 days, return 0
                 if eta > 99 * 24 * 60 * 60 {
                     0
@@ -85,11 +155,82 @@ impl HttpReceiver {
             0
         };
 
-        self.shower.print_status(completed, total, rate, eta)?;
-        self.ratestatus.clean();
+        self.control.set_progress(completed, total);
+        self.shower
+            .print_status(completed, total, rate, eta, self.control.connected())?;
+        if self.verbose {
+            let connections = self.connections.snapshot();
+            self.shower.print_details(&connections, self.blockmap.as_ref())?;
+        }
         Ok(())
     }
 
+    /// The summary of the received data, which is shown after the task succeeds
+    pub fn summary(&self, url: &str) -> Summary {
+        let average_rate = self.ratestatus.average_rate();
+        Summary {
+            url: url.to_owned(),
+            elapsed: self.ratestatus.elapsed(),
+            received: self.ratestatus.added(),
+            average_rate,
+            peak_rate: self.ratestatus.peak_rate().max(average_rate),
+            retries: self.control.retries(),
+            redownloaded: self.control.redownloaded(),
+        }
+    }
+
+    pub fn show_summary(&mut self, summary: &Summary) -> Result<()> {
+        self.shower.print_summary(summary)
+    }
+
+    /// p pauses or resumes, +/- change the concurrency, l/L lower or raise the speed limit and q stops
+    fn handle_key(&mut self, key: char) -> Result<()> {
+        let control = &self.control;
+        let msg = match key {
+            // The change is shown with the status
+            'p' => {
+                if control.is_paused() {
+                    control.resume();
+                } else {
+                    control.pause();
+                }
+                return self.show_status();
+            }
+            '+' | '=' => {
+                control.set_target(control.target() + 1);
+                format!("Concurrency: {}", control.target())
+            }
+            '-' => {
+                control.set_target(control.target().saturating_sub(1).max(1));
+                format!("Concurrency: {}", control.target())
+            }
+            'l' => {
+                // Start from the current speed if there is no limit
+                let limit = match control.limit() {
+                    0 => self.ratestatus.smoothed_rate() as u64,
+                    limit => limit,
+                };
+                control.set_limit((limit / 2).max(MIN_LIMIT));
+                format!("Speed limit: {}/s", control.limit().human_readable())
+            }
+            'L' => {
+                if control.limit() == 0 {
+                    "Speed limit: none".to_owned()
+                } else {
+                    control.set_limit(control.limit() * 2);
+                    format!("Speed limit: {}/s", control.limit().human_readable())
+                }
+            }
+            'q' => {
+                control.stop();
+                "Stopping...".to_owned()
+            }
+            _ => return Ok(()),
+        };
+        self.shower.print_msg(&msg)?;
+        self.show_status()
+    }
+
     fn record_pair(&mut self, pair: RangePair) -> Result<()> {
         if let Some(ref mut rangerecorder) = self.rangerecorder {
             rangerecorder.write_pair(pair)?;
@@ -101,16 +242,37 @@ impl HttpReceiver {
 
         let receiver = receiver.fuse();
         let tick = interval_stream(Duration::from_secs(2)).fuse();
+        let check = interval_stream(Duration::from_secs(1)).fuse();
+
+        // The terminal is restored when the reader is dropped
+        let (sender, keys) = unbounded();
+        let _keyreader = if self.interactive {
+            KeyReader::spawn(sender)
+        } else {
+            None
+        };
+        let keys = keys.fuse();
 
-        pin_mut!(receiver, tick);
+        pin_mut!(receiver, tick, check, keys);
 
+        // The download is aborted after the received data is written
+        let mut error = None;
         loop {
             select! {
                 item = receiver.next() => {
                     if let Some((pair, chunk)) = item {
                         self.output.write(&chunk[..], Some(SeekFrom::Start(pair.begin)))?;
                         self.record_pair(pair)?;
+                        if let Some(ref mut blockmap) = self.blockmap {
+                            blockmap.add(pair);
+                        }
                         self.ratestatus.add(pair.length());
+
+                        // The length is unknown for direct requests, so the file is checked while it grows
+                        if let Some(Err(err)) = self.limits.as_ref().map(|limits| limits.check_filesize(self.ratestatus.total())) {
+                            error = Some(err);
+                            break;
+                        }
                     } else {
                         break;
                     }
@@ -118,9 +280,85 @@ impl HttpReceiver {
                 _ = tick.next() => {
                     self.show_status()?;
                 },
+                _ = check.next() => {
+                    if let Err(err) = self.check_limits() {
+                        error = Some(err);
+                        break;
+                    }
+                },
+                key = keys.next() => {
+                    if let Some(key) = key {
+                        self.handle_key(key)?;
+                    }
+                },
             }
         }
+
+        // All received data is written, so the task can be resumed even if it is stopped
+        self.output.sync()?;
+        if let Some(ref mut rangerecorder) = self.rangerecorder {
+            rangerecorder.sync()?;
+        }
         self.show_status()?;
-        Ok(())
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpReceiver, MIN_LIMIT};
+    use crate::app::{
+        core::concurrency::ConcurrencyControl, show::common::ProgressFormat,
+        status::connection_status::ConnectionStatus,
+    };
+
+    #[test]
+    fn test_handle_key() {
+        let output = std::env::temp_dir().join(format!("aget-receiver-{}", std::process::id()));
+        let control = ConcurrencyControl::new(2, false);
+        let mut receiver = HttpReceiver::new(
+            &output,
+            true,
+            0,
+            control.clone(),
+            ConnectionStatus::new(),
+            ProgressFormat::Quiet,
+            false,
+        )
+        .unwrap();
+
+        // p pauses and resumes
+        receiver.handle_key('p').unwrap();
+        assert!(control.is_paused());
+        receiver.handle_key('p').unwrap();
+        assert!(!control.is_paused());
+
+        // +/= raise the concurrency and - lowers it, but not below one
+        receiver.handle_key('+').unwrap();
+        receiver.handle_key('=').unwrap();
+        assert_eq!(control.target(), 4);
+        for _ in 0..5 {
+            receiver.handle_key('-').unwrap();
+        }
+        assert_eq!(control.target(), 1);
+
+        // l starts from the lowest limit without any speed, L raises it and other keys are ignored
+        receiver.handle_key('l').unwrap();
+        assert_eq!(control.limit(), MIN_LIMIT);
+        receiver.handle_key('L').unwrap();
+        assert_eq!(control.limit(), MIN_LIMIT * 2);
+        receiver.handle_key('x').unwrap();
+        assert_eq!((control.target(), control.limit()), (1, MIN_LIMIT * 2));
+        assert!(!control.is_stopped());
+
+        // q stops the task
+        receiver.handle_key('q').unwrap();
+        assert!(control.is_stopped());
+
+        drop(receiver);
+        std::fs::remove_file(&output).unwrap();
     }
 }
@@ -4,9 +4,10 @@ use futures::{channel::mpsc::Receiver, pin_mut, select, StreamExt};
 
 use crate::{
     app::{
+        core::{concurrency::ConcurrencyControl, limits::DownloadLimits},
         record::{bytearray_recorder::ByteArrayRecorder, common::RECORDER_FILE_SUFFIX},
-        show::m3u8_show::M3u8Shower,
-        status::rate_status::RateStatus,
+        show::{common::ProgressFormat, m3u8_show::M3u8Shower},
+        status::{rate_status::RateStatus, summary::Summary},
     },
     common::{bytes::bytes_type::Bytes, errors::Result, file::File, time::interval_stream},
 };
@@ -16,13 +17,21 @@ pub struct M3u8Receiver {
     bytearrayrecorder: ByteArrayRecorder,
     ratestatus: RateStatus,
     shower: M3u8Shower,
+    control: ConcurrencyControl,
+    limits: Option<DownloadLimits>,
     total: u64,
     completed: u64,
     seek: u64,
+    // Whether the task is paused by signals, which is shown once it changes
+    paused: bool,
 }
 
 impl M3u8Receiver {
-    pub fn new<P: AsRef<Path>>(output: P) -> Result<M3u8Receiver> {
+    pub fn new<P: AsRef<Path>>(
+        output: P,
+        control: ConcurrencyControl,
+        progress: ProgressFormat,
+    ) -> Result<M3u8Receiver> {
         let mut outputfile = File::new(&output, true)?;
         outputfile.open()?;
 
@@ -39,41 +48,86 @@ impl M3u8Receiver {
             output: outputfile,
             bytearrayrecorder,
             ratestatus: RateStatus::new(),
-            shower: M3u8Shower::new(),
+            shower: M3u8Shower::new(progress),
+            control,
+            limits: None,
             total,
             completed,
             seek,
+            paused: false,
         })
     }
 
+    /// Abort the download when it exceeds the limits
+    pub fn with_limits(mut self, limits: DownloadLimits) -> M3u8Receiver {
+        self.limits = Some(limits);
+        self
+    }
+
+    fn check_limits(&mut self) -> Result<()> {
+        let (size, received, paused) = (self.seek, self.control.received(), self.control.is_paused());
+        match self.limits {
+            Some(ref mut limits) => limits.check(size, received, paused),
+            None => Ok(()),
+        }
+    }
+
     fn show_infos(&mut self) -> Result<()> {
         let file_name = &self.output.file_name().unwrap_or("[No Name]");
         let total = self.total;
-        self.shower.print_file(file_name)?;
-        self.shower.print_total(total)?;
+        self.shower.print_probe(file_name, total)?;
         self.show_status()?;
         Ok(())
     }
 
     fn show_status(&mut self) -> Result<()> {
+        if self.control.is_paused() != self.paused {
+            self.paused = !self.paused;
+            self.shower.print_msg(if self.paused { "Paused" } else { "Resumed" })?;
+        }
+
         let total = self.total;
         let completed = self.completed;
         let rate = self.ratestatus.rate();
         let length = self.seek;
 
-        self.shower.print_status(completed, total, length, rate)?;
+        // The total bytes of segments are unknown
+        self.control.set_progress(length, 0);
+        self.shower
+            .print_status(completed, total, length, rate, self.control.connected())?;
         self.ratestatus.clean();
         Ok(())
     }
 
+    /// The summary of the received data, which is shown after the task succeeds
+    pub fn summary(&self, url: &str) -> Summary {
+        let average_rate = self.ratestatus.average_rate();
+        Summary {
+            url: url.to_owned(),
+            elapsed: self.ratestatus.elapsed(),
+            received: self.ratestatus.added(),
+            average_rate,
+            peak_rate: self.ratestatus.peak_rate().max(average_rate),
+            retries: self.control.retries(),
+            redownloaded: self.control.redownloaded(),
+        }
+    }
+
+    pub fn show_summary(&mut self, summary: &Summary) -> Result<()> {
+        self.shower.print_summary(summary)
+    }
+
     pub async fn start(&mut self, receiver: Receiver<(u64, Bytes)>) -> Result<()> {
         self.show_infos()?;
 
         let receiver = receiver.fuse();
         let tick = interval_stream(Duration::from_secs(2)).fuse();
+        let check = interval_stream(Duration::from_secs(1)).fuse();
 
-        pin_mut!(receiver, tick);
+        pin_mut!(receiver, tick, check);
 
+        // The download is aborted after the received data is written
+        let mut error = None;
         loop {
             select! {
                 item = receiver.next() => {
@@ -91,6 +145,12 @@ impl M3u8Receiver {
 
                         // Update rate
                         self.ratestatus.add(len);
+
+                        // The total length of segments is unknown, so the file is checked while it grows
+                        if let Some(Err(err)) = self.limits.as_ref().map(|limits| limits.check_filesize(self.seek)) {
+                            error = Some(err);
+                            break;
+                        }
                     } else {
                         break;
                     }
@@ -98,9 +158,22 @@ impl M3u8Receiver {
                 _ = tick.next() => {
                     self.show_status()?;
                 },
+                _ = check.next() => {
+                    if let Err(err) = self.check_limits() {
+                        error = Some(err);
+                        break;
+                    }
+                },
             }
         }
+
+        // All received data is written, so the task can be resumed even if it is stopped
+        self.output.sync()?;
+        self.bytearrayrecorder.sync()?;
         self.show_status()?;
-        Ok(())
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 }
@@ -41,6 +41,11 @@ impl ByteArrayRecorder {
         self.inner.remove()
     }
 
+    /// Write the records to the disk
+    pub fn sync(&mut self) -> Result<()> {
+        self.inner.sync()
+    }
+
     /// Read the index-th number
     pub fn index(&mut self, index: u64) -> Result<u64> {
         let mut buf: [u8; 8] = [0; 8];
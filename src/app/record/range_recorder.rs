@@ -43,6 +43,11 @@ impl RangeRecorder {
         self.inner.remove()
     }
 
+    /// Write the records to the disk
+    pub fn sync(&mut self) -> Result<()> {
+        self.inner.sync()
+    }
+
     /// Get downloading file's content length stored in the aget file
     pub fn total(&mut self) -> Result<u64> {
         let mut buf: [u8; 8] = [0; 8];
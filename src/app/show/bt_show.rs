@@ -1,7 +1,13 @@
-use std::io::{stdout, Stdout, Write};
+use std::{io::Write, time::Instant};
 
 use crate::{
-    app::show::common::du_bars,
+    app::{
+        show::{
+            common::{du_bars, output, plain_line_due, print_summary, styled, use_color, ProgressFormat},
+            event::{emit, known_eta, Event, FileInfo, FileProgress, Peers, Status},
+        },
+        status::summary::Summary,
+    },
     common::{
         colors::{Black, Blue, Cyan, Green, Red, Yellow, RGB},
         errors::Result,
@@ -12,21 +18,77 @@ use crate::{
 };
 
 pub struct BtShower {
-    stdout: Stdout,
+    stdout: Box<dyn Write + Send>,
+    progress: ProgressFormat,
+    color: bool,
+    // The time of the last plain status line
+    last_line: Option<Instant>,
 }
 
 impl BtShower {
-    pub fn new() -> BtShower {
-        BtShower { stdout: stdout() }
+    pub fn new(progress: ProgressFormat) -> BtShower {
+        BtShower {
+            stdout: output(progress),
+            progress,
+            color: progress == ProgressFormat::Bar && use_color(),
+            last_line: None,
+        }
+    }
+
+    pub fn print_summary(&mut self, summary: &Summary) -> Result<()> {
+        print_summary(&mut self.stdout, self.progress, self.color, summary)
     }
 
     pub fn print_msg(&mut self, msg: &str) -> Result<()> {
-        writeln!(&mut self.stdout, "\n  {}", Yellow.italic().paint(msg))?;
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Message { message: msg });
+        }
+        writeln!(
+            &mut self.stdout,
+            "\n  {}",
+            styled(self.color, Yellow.italic()).paint(msg)
+        )?;
         Ok(())
     }
 
+    /// Print the torrent name and its files
+    pub fn print_probe(&mut self, name: &str, files: &[(&str, u64, bool)]) -> Result<()> {
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Probe {
+                name,
+                total: Some(
+                    files
+                        .iter()
+                        .filter(|(_, _, included)| *included)
+                        .map(|(_, length, _)| length)
+                        .sum(),
+                ),
+                ranges: None,
+                segments: None,
+                files: Some(
+                    files
+                        .iter()
+                        .map(|(name, length, included)| FileInfo {
+                            name,
+                            length: *length,
+                            included: *included,
+                        })
+                        .collect(),
+                ),
+            });
+        }
+
+        self.print_name(name)?;
+        self.print_files(files)
+    }
+
     pub fn print_name(&mut self, name: &str) -> Result<()> {
-        writeln!(&mut self.stdout, "\n{}: {}", Green.bold().paint("Torrent Name"), name,)?;
+        writeln!(
+            &mut self.stdout,
+            "\n{}: {}",
+            styled(self.color, Green.bold()).paint("Torrent Name"),
+            name,
+        )?;
         Ok(())
     }
 
@@ -36,11 +98,11 @@ impl BtShower {
                 &mut self.stdout,
                 "{} {}: {} ({})",
                 if *included {
-                    Green.bold().paint("✓")
+                    styled(self.color, Green.bold()).paint("✓")
                 } else {
-                    Red.bold().paint("✘")
+                    styled(self.color, Red.bold()).paint("✘")
                 },
-                Blue.bold().paint("File"),
+                styled(self.color, Blue.bold()).paint("File"),
                 filename,
                 length.human_readable(),
             )?;
@@ -58,7 +120,36 @@ impl BtShower {
         uploaded: u64,
         live: usize,
         queued: usize,
+        // (name, completed, length) of included files
+        files: &[(&str, u64, u64)],
     ) -> Result<()> {
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Status(Status {
+                completed,
+                total: Some(total),
+                rate: down_rate,
+                eta: known_eta(eta, completed, total),
+                connections: Some(live as u64),
+                upload_rate: Some(up_rate),
+                uploaded: Some(uploaded),
+                peers: Some(Peers {
+                    live: live as u64,
+                    queued: queued as u64,
+                }),
+                files: Some(
+                    files
+                        .iter()
+                        .map(|(name, completed, length)| FileProgress {
+                            name,
+                            completed: *completed,
+                            length: *length,
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            }));
+        }
+
         let percent = if total != 0 {
             completed as f64 / total as f64
         } else {
@@ -86,6 +177,14 @@ impl BtShower {
             eta = eta_str,
         );
 
+        if self.progress == ProgressFormat::Plain {
+            if plain_line_due(&mut self.last_line, total != 0 && completed >= total) {
+                writeln!(&mut self.stdout, "{}", info)?;
+                self.stdout.flush()?;
+            }
+            return Ok(());
+        }
+
         // set default info length
         let info_length = 80;
         let mut miss = info_length - info.len();
@@ -109,21 +208,21 @@ impl BtShower {
         write!(
             &mut self.stdout,
             "\r{completed}/{total} {percent}% ↓{down_rate}/s ↑{up_rate}/s({uploaded}) eta: {eta} peers: {live}/{queued}{miss} {bar_done}{bar_undone}  ",
-            completed = Red.bold().paint(completed_str),
-            total = Green.bold().paint(total_str),
-            percent = Yellow.bold().paint(percent_str),
-            down_rate = Blue.bold().paint(down_rate_str),
-            up_rate = RGB(0x66, 0x00, 0xcc).bold().paint(up_rate_str),
+            completed = styled(self.color, Red.bold()).paint(completed_str),
+            total = styled(self.color, Green.bold()).paint(total_str),
+            percent = styled(self.color, Yellow.bold()).paint(percent_str),
+            down_rate = styled(self.color, Blue.bold()).paint(down_rate_str),
+            up_rate = styled(self.color, RGB(0x66, 0x00, 0xcc).bold()).paint(up_rate_str),
             uploaded = uploaded_str,
-            eta = Cyan.bold().paint(eta_str),
+            eta = styled(self.color, Cyan.bold()).paint(eta_str),
             miss = " ".repeat(miss),
             bar_done = if total != 0 {
-                Red.bold().paint(bar_done_str).to_string()
+                styled(self.color, Red.bold()).paint(bar_done_str).to_string()
             } else {
                 bar_done_str
             },
             bar_undone = if total != 0 {
-                Black.bold().paint(bar_undone_str).to_string()
+                styled(self.color, Black.bold()).paint(bar_undone_str).to_string()
             } else {
                 bar_undone_str
             }
@@ -132,8 +231,17 @@ impl BtShower {
         Ok(())
     }
 
-    pub fn print_completed_file(&mut self, name: &str) -> Result<()> {
-        writeln!(&mut self.stdout, "\n{}: {}", Green.italic().paint("Completed"), name,)?;
+    pub fn print_completed_file(&mut self, name: &str, length: u64) -> Result<()> {
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::FileCompleted { name, length });
+        }
+
+        writeln!(
+            &mut self.stdout,
+            "\n{}: {}",
+            styled(self.color, Green.italic()).paint("Completed"),
+            name,
+        )?;
         self.stdout.flush()?;
         Ok(())
     }
@@ -1,3 +1,20 @@
+use std::{
+    io::{sink, stdout, Write},
+    time::{Duration, Instant},
+};
+
+use ansi_term::Style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::status::summary::Summary,
+    common::{
+        colors::{Blue, Cyan, Green, Red, Yellow},
+        errors::Result,
+        size::HumanReadable,
+    },
+};
+
 #[cfg(target_os = "windows")]
 pub fn bars() -> (&'static str, &'static str, &'static str) {
     // bar, bar_right, bar_left
@@ -33,3 +50,98 @@ pub fn du_bars(bar_done_length: usize, bar_undone_length: usize) -> (String, Str
 
     (bar_done_str, bar_undone_str)
 }
+
+/// The format of the progress output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressFormat {
+    /// The progress bar for terminals
+    #[default]
+    Bar,
+    /// Periodic status lines without `\r` and colors, for logs
+    Plain,
+    /// Newline-delimited json events, see `app::show::event::Event`
+    Json,
+    /// No output except errors
+    Quiet,
+}
+
+/// Whether colors are allowed. See https://no-color.org
+pub fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// The style if colors are allowed, or no style
+pub fn styled(color: bool, style: Style) -> Style {
+    if color {
+        style
+    } else {
+        Style::new()
+    }
+}
+
+/// The output of showers, which discards everything in quiet mode
+pub fn output(progress: ProgressFormat) -> Box<dyn Write + Send> {
+    if progress == ProgressFormat::Quiet {
+        Box::new(sink())
+    } else {
+        Box::new(stdout())
+    }
+}
+
+/// The interval of plain status lines
+const PLAIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether a plain status line should be printed. Lines are printed every `PLAIN_INTERVAL`, and
+/// when the task is done.
+pub fn plain_line_due(last: &mut Option<Instant>, done: bool) -> bool {
+    if done || last.is_none_or(|last| last.elapsed() >= PLAIN_INTERVAL) {
+        *last = Some(Instant::now());
+        true
+    } else {
+        false
+    }
+}
+
+/// Print the summary of a completed task. Json events carry the summary in the `finished` event.
+pub fn print_summary(out: &mut dyn Write, progress: ProgressFormat, color: bool, summary: &Summary) -> Result<()> {
+    if progress == ProgressFormat::Json || progress == ProgressFormat::Quiet {
+        return Ok(());
+    }
+
+    let rows = [
+        (
+            styled(color, Green.bold()),
+            "Elapsed",
+            format!("{:.2}s", summary.elapsed.as_secs_f64()),
+        ),
+        (
+            styled(color, Blue.bold()),
+            "Average speed",
+            format!(
+                "{}/s ({} received)",
+                summary.average_rate.human_readable(),
+                summary.received.human_readable()
+            ),
+        ),
+        (
+            styled(color, Blue.bold()),
+            "Peak speed",
+            format!("{}/s", summary.peak_rate.human_readable()),
+        ),
+        (styled(color, Yellow.bold()), "Retries", summary.retries.to_string()),
+        (
+            styled(color, Red.bold()),
+            "Re-downloaded",
+            format!("{} ({})", summary.redownloaded.human_readable(), summary.redownloaded),
+        ),
+        (styled(color, Cyan.bold()), "URL", summary.url.clone()),
+    ];
+
+    writeln!(out)?;
+    for (style, name, value) in rows.iter() {
+        writeln!(out, "{}: {}", style.paint(*name), value)?;
+    }
+    out.flush()?;
+    Ok(())
+}
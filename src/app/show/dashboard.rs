@@ -0,0 +1,338 @@
+use std::{
+    io::{stdout, IsTerminal, Stdout, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::{
+    app::{
+        core::batch::{BatchTask, TaskState},
+        show::common::{du_bars, styled, use_color},
+        status::rate_status::RateStatus,
+    },
+    common::{
+        colors::{Black, Blue, Cyan, Green, Red, Yellow},
+        errors::{Error, Result},
+        liberal::ToDate,
+        size::HumanReadable,
+        tasks::TaskType,
+    },
+};
+
+/// The interval of updating the rates of tasks, which is the interval of receivers' status
+const UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The length of the progress bar of a task
+const BAR_LENGTH: usize = 16;
+
+const KEYS: &str = " up/down select  p pause/resume  c cancel  +/- concurrency  q quit";
+
+/// The rate of a task, which is measured by the completed bytes
+#[derive(Default)]
+struct RowStatus {
+    ratestatus: RateStatus,
+    // Whether the resumed bytes are known, which are not counted in the rate
+    started: bool,
+    rate: f64,
+}
+
+/// Enter the alternate screen of the terminal in raw mode, and restore it when it is dropped
+struct Screen {
+    stdout: Stdout,
+}
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        let mut stdout = stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+        Ok(Screen { stdout })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.stdout, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The full-screen table of the tasks of `--tui`
+pub struct Dashboard {
+    tasks: Vec<Arc<BatchTask>>,
+    rows: Vec<RowStatus>,
+    selected: usize,
+    color: bool,
+    quitting: bool,
+    screen: Option<Screen>,
+}
+
+impl Dashboard {
+    pub fn new(tasks: Vec<Arc<BatchTask>>) -> Result<Dashboard> {
+        if !stdout().is_terminal() {
+            return Err(Error::NoTerminal);
+        }
+        let rows = tasks.iter().map(|_| RowStatus::default()).collect();
+        Ok(Dashboard {
+            tasks,
+            rows,
+            selected: 0,
+            color: use_color(),
+            quitting: false,
+            screen: Some(Screen::enter()?),
+        })
+    }
+
+    /// Show the tasks and handle keys until all tasks are finished, then print the results
+    pub fn run(&mut self) -> Result<()> {
+        let mut last_update: Option<Instant> = None;
+        loop {
+            if last_update.is_none_or(|last| last.elapsed() >= UPDATE_INTERVAL) {
+                self.update();
+                last_update = Some(Instant::now());
+            }
+            self.draw()?;
+
+            if self.tasks.iter().all(|task| task.state().is_finished()) {
+                break;
+            }
+
+            if event::poll(Duration::from_millis(200))? {
+                if let TermEvent::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key);
+                    }
+                }
+            }
+        }
+
+        // Leave the screen
+        self.screen = None;
+        self.print_results()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let task = &self.tasks[self.selected];
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit(),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(self.tasks.len() - 1),
+            KeyCode::Char('p') => {
+                if task.control.is_paused() {
+                    task.control.resume();
+                } else {
+                    task.control.pause();
+                }
+            }
+            KeyCode::Char('c') => task.control.stop(),
+            KeyCode::Char('+') | KeyCode::Char('=') => task.control.set_target(task.control.target() + 1),
+            KeyCode::Char('-') => task.control.set_target(task.control.target().saturating_sub(1).max(1)),
+            KeyCode::Char('q') | KeyCode::Esc => self.quit(),
+            _ => {}
+        }
+    }
+
+    /// Stop all tasks. The dashboard exits when they are finished.
+    fn quit(&mut self) {
+        self.quitting = true;
+        self.tasks.iter().for_each(|task| task.control.stop());
+    }
+
+    fn update(&mut self) {
+        for (task, row) in self.tasks.iter().zip(self.rows.iter_mut()) {
+            let completed = task.control.completed();
+            if !row.started {
+                // The first progress of the task is the resumed bytes
+                if completed != 0 || task.control.total() != 0 {
+                    row.ratestatus.set_total(completed);
+                    row.started = true;
+                }
+            } else if completed >= row.ratestatus.total() {
+                row.ratestatus.add(completed - row.ratestatus.total());
+            }
+            row.rate = if task.state() == TaskState::Running && !task.control.is_paused() {
+                row.ratestatus.rate()
+            } else {
+                0.0
+            };
+            row.ratestatus.clean();
+        }
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+
+        let mut lines = vec![self.header(), "".to_owned(), self.title(width)];
+
+        // Scroll to the selected task
+        let visible = height.saturating_sub(5).max(1);
+        let skip = (self.selected + 1).saturating_sub(visible);
+        for (i, task) in self.tasks.iter().enumerate().skip(skip).take(visible) {
+            lines.push(self.row(i, task, width));
+        }
+        lines.push("".to_owned());
+        lines.push(styled(self.color, Black.bold()).paint(KEYS).to_string());
+
+        let out = &mut self.screen.as_mut().unwrap().stdout;
+        queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+        write!(out, "{}", lines.join("\r\n"))?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn header(&self) -> String {
+        let count = |f: fn(&TaskState) -> bool| self.tasks.iter().filter(|task| f(&task.state())).count();
+        let rate: f64 = self.rows.iter().map(|row| row.rate).sum();
+        format!(
+            " {} {} tasks: {} running, {} done, {} failed  {}/s{}",
+            styled(self.color, Green.bold()).paint("aget"),
+            self.tasks.len(),
+            count(|state| *state == TaskState::Running),
+            count(|state| *state == TaskState::Done),
+            count(|state| matches!(state, TaskState::Failed(_))),
+            styled(self.color, Blue.bold()).paint(rate.human_readable()),
+            if self.quitting { "  stopping..." } else { "" },
+        )
+    }
+
+    fn name_width(width: usize) -> usize {
+        // The width of other columns, and at least 12 characters of the status
+        width.saturating_sub(10 + 1 + BAR_LENGTH + 53 + 12).max(8)
+    }
+
+    fn title(&self, width: usize) -> String {
+        let line = format!(
+            " {:>3} {:<4} {:<name$} {:<bar$} {:>7} {:>17} {:>10} {:>5} {:>7}  {}",
+            "#",
+            "TYPE",
+            "NAME",
+            "PROGRESS",
+            "",
+            "SIZE",
+            "SPEED",
+            "ETA",
+            "CONN",
+            "STATUS",
+            name = Dashboard::name_width(width),
+            bar = BAR_LENGTH,
+        );
+        styled(self.color, Yellow.bold())
+            .paint(truncate(&line, width))
+            .to_string()
+    }
+
+    fn row(&self, index: usize, task: &BatchTask, width: usize) -> String {
+        let row = &self.rows[index];
+        let state = task.state();
+        let control = &task.control;
+        let (completed, total) = (control.completed(), control.total());
+
+        let (bar, percent) = if total != 0 {
+            let percent = completed as f64 / total as f64;
+            let done = (BAR_LENGTH as f64 * percent) as usize;
+            let (done, undone) = du_bars(done, BAR_LENGTH - done);
+            (
+                format!(
+                    "{}{}",
+                    styled(self.color, Red.bold()).paint(done),
+                    styled(self.color, Black.bold()).paint(undone)
+                ),
+                format!("{:.2}%", percent * 100.0),
+            )
+        } else {
+            (" ".repeat(BAR_LENGTH), "-".to_owned())
+        };
+
+        let size = if total != 0 {
+            format!("{}/{}", completed.human_readable(), total.human_readable())
+        } else {
+            completed.human_readable()
+        };
+
+        let (rate, eta) = if state == TaskState::Running {
+            let smoothed = row.ratestatus.smoothed_rate();
+            let eta = if total > completed && smoothed > 1.0 {
+                ((total - completed) as f64 / smoothed) as u64
+            } else {
+                0
+            };
+            (format!("{}/s", row.rate.human_readable()), eta.date())
+        } else {
+            ("-".to_owned(), "-".to_owned())
+        };
+
+        // Peers of torrents are managed by the session
+        let connections = match task.task_type {
+            TaskType::BT => "-".to_owned(),
+            _ if state == TaskState::Running => format!("{}/{}", control.connected(), control.target()),
+            _ => format!("{}", control.target()),
+        };
+
+        let (status, style) = match state {
+            TaskState::Running if control.is_stopped() => ("stopping".to_owned(), Yellow.normal()),
+            TaskState::Running if control.is_paused() => ("paused".to_owned(), Yellow.normal()),
+            TaskState::Pending if control.is_paused() => ("paused".to_owned(), Yellow.normal()),
+            TaskState::Pending => ("pending".to_owned(), Black.bold()),
+            TaskState::Running => ("running".to_owned(), Cyan.normal()),
+            TaskState::Done => ("done".to_owned(), Green.normal()),
+            TaskState::Failed(err) => (format!("failed: {}", err), Red.normal()),
+            TaskState::Cancelled => ("cancelled".to_owned(), Black.bold()),
+        };
+
+        let name_width = Dashboard::name_width(width);
+        let head = format!(
+            " {:>3} {:<4} {:<name$}",
+            index + 1,
+            match task.task_type {
+                TaskType::HTTP => "http",
+                TaskType::M3U8 => "m3u8",
+                TaskType::BT => "bt",
+            },
+            truncate(&task.name, name_width),
+            name = name_width,
+        );
+        let tail = format!(
+            " {:>7} {:>17} {:>10} {:>5} {:>7}  ",
+            percent, size, rate, eta, connections
+        );
+        // The bar and the status are not counted in the width of other columns
+        let status_width = width.saturating_sub(head.chars().count() + 1 + BAR_LENGTH + tail.chars().count());
+        let status = styled(self.color, style).paint(truncate(&status, status_width));
+
+        // Mark the selected task
+        let head = if index == self.selected {
+            styled(self.color, Blue.reverse()).paint(head).to_string()
+        } else {
+            head
+        };
+        format!("{} {}{}{}", head, bar, tail, status)
+    }
+
+    fn print_results(&self) -> Result<()> {
+        let mut stdout = stdout();
+        for task in self.tasks.iter() {
+            let state = match task.state() {
+                TaskState::Done => styled(self.color, Green.bold()).paint("Done").to_string(),
+                TaskState::Failed(err) => format!("{}: {}", styled(self.color, Red.bold()).paint("Failed"), err),
+                TaskState::Cancelled => styled(self.color, Yellow.bold()).paint("Cancelled").to_string(),
+                // Not reachable, all tasks are finished
+                TaskState::Pending | TaskState::Running => "Unfinished".to_owned(),
+            };
+            writeln!(stdout, "{} {}", task.name, state)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cut the string to the width of characters
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
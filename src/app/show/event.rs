@@ -0,0 +1,155 @@
+use std::{
+    io::{stdout, Error as IoError, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{app::status::summary::Summary, common::errors::Result};
+
+/// A progress event, which is printed as a line of json for `--progress json`
+///
+/// Every line has the `event` name and the unix `time` in milliseconds, e.g.
+///   {"event":"status","completed":1024,"total":4096,"rate":512.0,"eta":6,"connections":4,"time":1700000000000}
+///
+/// Http, m3u8 and bt tasks share the schema. The fields which a task doesn't have are null or absent.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A task starts. `task` is one of "http", "m3u8" and "bt"
+    Start {
+        task: &'a str,
+        url: &'a str,
+        output: &'a str,
+    },
+
+    /// The result of probing the resource
+    Probe {
+        /// The output file name, or the torrent name
+        name: &'a str,
+        /// Total bytes
+        total: Option<u64>,
+        /// Whether the server supports range requests
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ranges: Option<bool>,
+        /// The number of m3u8 segments
+        #[serde(skip_serializing_if = "Option::is_none")]
+        segments: Option<u64>,
+        /// Files of the torrent
+        #[serde(skip_serializing_if = "Option::is_none")]
+        files: Option<Vec<FileInfo<'a>>>,
+    },
+
+    /// The periodic status
+    Status(Status<'a>),
+
+    /// A message for users
+    Message { message: &'a str },
+
+    /// The task fails and will be retried after `wait` seconds
+    Retry {
+        attempt: u64,
+        retries: u64,
+        wait: f64,
+        error: String,
+    },
+
+    /// A file of the torrent is completed
+    FileCompleted { name: &'a str, length: u64 },
+
+    /// The task succeeds. The summary is absent if the task was completed before
+    Finished {
+        elapsed: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        summary: Option<Summary>,
+    },
+
+    /// The task fails without more retries
+    Error { error: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileInfo<'a> {
+    pub name: &'a str,
+    pub length: u64,
+    pub included: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Status<'a> {
+    /// Completed bytes
+    pub completed: u64,
+    /// Total bytes, null if it is unknown
+    pub total: Option<u64>,
+    /// Bytes per second
+    pub rate: f64,
+    /// Estimated seconds to complete, null if it is unknown
+    pub eta: Option<u64>,
+    /// The number of active connections, or live peers of the torrent
+    pub connections: Option<u64>,
+
+    /// Completed and total m3u8 segments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Progress>,
+
+    /// Upload bytes per second of the torrent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_rate: Option<f64>,
+    /// Uploaded bytes of the torrent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uploaded: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<Peers>,
+    /// Progress of included files of the torrent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<FileProgress<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Progress {
+    pub completed: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Peers {
+    pub live: u64,
+    pub queued: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileProgress<'a> {
+    pub name: &'a str,
+    pub completed: u64,
+    pub length: u64,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+    time: u128,
+}
+
+/// Print the event as a line of json to stdout
+pub fn emit(event: &Event) -> Result<()> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let line = serde_json::to_string(&Line { event, time }).map_err(IoError::from)?;
+
+    let mut stdout = stdout().lock();
+    writeln!(stdout, "{}", line)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The eta given to showers is 0 when it is unknown. Return `None` for that.
+pub fn known_eta(eta: u64, completed: u64, total: u64) -> Option<u64> {
+    if eta != 0 || (total != 0 && completed >= total) {
+        Some(eta)
+    } else {
+        None
+    }
+}
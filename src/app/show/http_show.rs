@@ -1,7 +1,13 @@
-use std::io::{stdout, Stdout, Write};
+use std::{io::Write, time::Instant};
 
 use crate::{
-    app::show::common::du_bars,
+    app::{
+        show::{
+            common::{du_bars, output, plain_line_due, print_summary, styled, use_color, ProgressFormat},
+            event::{emit, known_eta, Event, Status},
+        },
+        status::{block_map::BlockMap, connection_status::ConnectionInfo, summary::Summary},
+    },
     common::{
         colors::{Black, Blue, Cyan, Green, Red, Yellow},
         errors::Result,
@@ -12,21 +18,73 @@ use crate::{
 };
 
 pub struct HttpShower {
-    stdout: Stdout,
+    stdout: Box<dyn Write + Send>,
+    progress: ProgressFormat,
+    color: bool,
+    // The time of the last plain status line
+    last_line: Option<Instant>,
+    // Whether the last status line is printed in the plain format
+    status_printed: bool,
+    // The number of lines of details below the status line
+    detail_lines: usize,
 }
 
 impl HttpShower {
-    pub fn new() -> HttpShower {
-        HttpShower { stdout: stdout() }
+    pub fn new(progress: ProgressFormat) -> HttpShower {
+        HttpShower {
+            stdout: output(progress),
+            progress,
+            color: progress == ProgressFormat::Bar && use_color(),
+            last_line: None,
+            status_printed: false,
+            detail_lines: 0,
+        }
+    }
+
+    pub fn print_summary(&mut self, summary: &Summary) -> Result<()> {
+        print_summary(&mut self.stdout, self.progress, self.color, summary)
     }
 
     pub fn print_msg(&mut self, msg: &str) -> Result<()> {
-        writeln!(&mut self.stdout, "\n  {}", Yellow.italic().paint(msg))?;
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Message { message: msg });
+        }
+        writeln!(
+            &mut self.stdout,
+            "\n  {}",
+            styled(self.color, Yellow.italic()).paint(msg)
+        )?;
+        // The details are kept above the message
+        self.detail_lines = 0;
         Ok(())
     }
 
+    /// Print the output file, the content length and whether the server supports range requests
+    pub fn print_probe(&mut self, path: &str, total: u64, ranges: bool) -> Result<()> {
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Probe {
+                name: path,
+                total: if total != 0 { Some(total) } else { None },
+                ranges: Some(ranges),
+                segments: None,
+                files: None,
+            });
+        }
+
+        if !ranges {
+            self.print_msg("Server doesn't support range request.")?;
+        }
+        self.print_file(path)?;
+        self.print_total(total)
+    }
+
     pub fn print_file(&mut self, path: &str) -> Result<()> {
-        writeln!(&mut self.stdout, "\n{}: {}", Green.bold().paint("File"), path,)?;
+        writeln!(
+            &mut self.stdout,
+            "\n{}: {}",
+            styled(self.color, Green.bold()).paint("File"),
+            path,
+        )?;
         Ok(())
     }
 
@@ -34,7 +92,7 @@ impl HttpShower {
         writeln!(
             &mut self.stdout,
             "{}: {} ({})",
-            Blue.bold().paint("Length"),
+            styled(self.color, Blue.bold()).paint("Length"),
             total.human_readable(),
             total,
         )?;
@@ -45,13 +103,24 @@ impl HttpShower {
         writeln!(
             &mut self.stdout,
             "{}: {}\n",
-            Yellow.bold().paint("concurrency"),
+            styled(self.color, Yellow.bold()).paint("concurrency"),
             concurrency,
         )?;
         Ok(())
     }
 
-    pub fn print_status(&mut self, completed: u64, total: u64, rate: f64, eta: u64) -> Result<()> {
+    pub fn print_status(&mut self, completed: u64, total: u64, rate: f64, eta: u64, connections: u64) -> Result<()> {
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Status(Status {
+                completed,
+                total: if total != 0 { Some(total) } else { None },
+                rate,
+                eta: known_eta(eta, completed, total),
+                connections: Some(connections),
+                ..Default::default()
+            }));
+        }
+
         let percent = if total != 0 {
             completed as f64 / total as f64
         } else {
@@ -75,6 +144,15 @@ impl HttpShower {
             eta = eta_str,
         );
 
+        if self.progress == ProgressFormat::Plain {
+            self.status_printed = plain_line_due(&mut self.last_line, total != 0 && completed >= total);
+            if self.status_printed {
+                writeln!(&mut self.stdout, "{}", info)?;
+                self.stdout.flush()?;
+            }
+            return Ok(());
+        }
+
         // set default info length
         let info_length = 41;
         let mut miss = info_length - info.len();
@@ -95,22 +173,28 @@ impl HttpShower {
             (" ".repeat(bar_length as usize), "".to_owned())
         };
 
+        // Move up to the status line and clear the details
+        if self.detail_lines > 0 {
+            write!(&mut self.stdout, "\x1b[{}A\r\x1b[J", self.detail_lines)?;
+            self.detail_lines = 0;
+        }
+
         write!(
             &mut self.stdout,
             "\r{completed}/{total} {percent}% {rate}/s eta: {eta}{miss} {bar_done}{bar_undone}  ",
-            completed = Red.bold().paint(completed_str),
-            total = Green.bold().paint(total_str),
-            percent = Yellow.bold().paint(percent_str),
-            rate = Blue.bold().paint(rate_str),
-            eta = Cyan.bold().paint(eta_str),
+            completed = styled(self.color, Red.bold()).paint(completed_str),
+            total = styled(self.color, Green.bold()).paint(total_str),
+            percent = styled(self.color, Yellow.bold()).paint(percent_str),
+            rate = styled(self.color, Blue.bold()).paint(rate_str),
+            eta = styled(self.color, Cyan.bold()).paint(eta_str),
             miss = " ".repeat(miss),
             bar_done = if total != 0 {
-                Red.bold().paint(bar_done_str).to_string()
+                styled(self.color, Red.bold()).paint(bar_done_str).to_string()
             } else {
                 bar_done_str
             },
             bar_undone = if total != 0 {
-                Black.bold().paint(bar_undone_str).to_string()
+                styled(self.color, Black.bold()).paint(bar_undone_str).to_string()
             } else {
                 bar_undone_str
             }
@@ -118,6 +202,59 @@ impl HttpShower {
 
         self.stdout.flush()?;
 
+        Ok(())
+    }
+    /// Print the status of each connection and the map of downloaded blocks below the status line
+    pub fn print_details(&mut self, connections: &[ConnectionInfo], blockmap: Option<&BlockMap>) -> Result<()> {
+        match self.progress {
+            ProgressFormat::Json | ProgressFormat::Quiet => return Ok(()),
+            ProgressFormat::Plain if !self.status_printed => return Ok(()),
+            _ => {}
+        }
+
+        // Lines must not be wrapped, or they can't be cleared
+        let width = terminal_width() as usize - 1;
+
+        let mut lines = vec![];
+        for connection in connections.iter() {
+            let range = match connection.range {
+                Some(range) => format!("{}-{}", range.begin.human_readable(), (range.end + 1).human_readable()),
+                None => "-".to_owned(),
+            };
+            let ranges = if connection.ranges > 1 {
+                format!(" ({} ranges)", connection.ranges)
+            } else {
+                "".to_owned()
+            };
+            lines.push(format!(
+                "  #{:<3} {}{}  {}/{}  {}/s  retries: {}",
+                connection.id,
+                range,
+                ranges,
+                connection.done.human_readable(),
+                connection.length.human_readable(),
+                connection.rate.human_readable(),
+                connection.retries,
+            ));
+        }
+        if let Some(blockmap) = blockmap {
+            lines.extend(blockmap.render(width - 2).into_iter().map(|line| format!("  {}", line)));
+        }
+        let lines: Vec<String> = lines
+            .into_iter()
+            .map(|line| line.chars().take(width).collect())
+            .collect();
+
+        if self.progress == ProgressFormat::Plain {
+            for line in lines.iter() {
+                writeln!(&mut self.stdout, "{}", line)?;
+            }
+        } else if !lines.is_empty() {
+            write!(&mut self.stdout, "\n{}", lines.join("\n"))?;
+            self.detail_lines = lines.len();
+        }
+        self.stdout.flush()?;
+
         Ok(())
     }
 }
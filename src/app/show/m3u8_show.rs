@@ -1,7 +1,13 @@
-use std::io::{stdout, Stdout, Write};
+use std::{io::Write, time::Instant};
 
 use crate::{
-    app::show::common::du_bars,
+    app::{
+        show::{
+            common::{du_bars, output, plain_line_due, print_summary, styled, use_color, ProgressFormat},
+            event::{emit, Event, Progress, Status},
+        },
+        status::summary::Summary,
+    },
     common::{
         colors::{Black, Blue, Green, Red, Yellow},
         errors::Result,
@@ -11,32 +17,73 @@ use crate::{
 };
 
 pub struct M3u8Shower {
-    stdout: Stdout,
+    stdout: Box<dyn Write + Send>,
+    progress: ProgressFormat,
+    color: bool,
+    // The time of the last plain status line
+    last_line: Option<Instant>,
 }
 
 impl M3u8Shower {
-    pub fn new() -> M3u8Shower {
-        M3u8Shower { stdout: stdout() }
+    pub fn new(progress: ProgressFormat) -> M3u8Shower {
+        M3u8Shower {
+            stdout: output(progress),
+            progress,
+            color: progress == ProgressFormat::Bar && use_color(),
+            last_line: None,
+        }
+    }
+
+    pub fn print_summary(&mut self, summary: &Summary) -> Result<()> {
+        print_summary(&mut self.stdout, self.progress, self.color, summary)
     }
 
     pub fn print_msg(&mut self, msg: &str) -> Result<()> {
-        writeln!(&mut self.stdout, "\n  {}", Yellow.italic().paint(msg))?;
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Message { message: msg });
+        }
+        writeln!(
+            &mut self.stdout,
+            "\n  {}",
+            styled(self.color, Yellow.italic()).paint(msg)
+        )?;
         Ok(())
     }
 
+    /// Print the output file and the number of segments
+    pub fn print_probe(&mut self, path: &str, total: u64) -> Result<()> {
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Probe {
+                name: path,
+                total: None,
+                ranges: None,
+                segments: Some(total),
+                files: None,
+            });
+        }
+
+        self.print_file(path)?;
+        self.print_total(total)
+    }
+
     pub fn print_file(&mut self, path: &str) -> Result<()> {
         writeln!(
             &mut self.stdout,
             // "\n     {}: {}",
             "\n{}: {}",
-            Green.bold().paint("File"),
+            styled(self.color, Green.bold()).paint("File"),
             path,
         )?;
         Ok(())
     }
 
     pub fn print_total(&mut self, total: u64) -> Result<()> {
-        writeln!(&mut self.stdout, "{}: {}", Blue.bold().paint("Segments"), total,)?;
+        writeln!(
+            &mut self.stdout,
+            "{}: {}",
+            styled(self.color, Blue.bold()).paint("Segments"),
+            total,
+        )?;
         Ok(())
     }
 
@@ -44,13 +91,26 @@ impl M3u8Shower {
         writeln!(
             &mut self.stdout,
             "{}: {}\n",
-            Yellow.bold().paint("concurrency"),
+            styled(self.color, Yellow.bold()).paint("concurrency"),
             concurrency,
         )?;
         Ok(())
     }
 
-    pub fn print_status(&mut self, completed: u64, total: u64, length: u64, rate: f64) -> Result<()> {
+    pub fn print_status(&mut self, completed: u64, total: u64, length: u64, rate: f64, connections: u64) -> Result<()> {
+        // The total bytes of segments are unknown
+        if self.progress == ProgressFormat::Json {
+            return emit(&Event::Status(Status {
+                completed: length,
+                total: None,
+                rate,
+                eta: None,
+                connections: Some(connections),
+                segments: Some(Progress { completed, total }),
+                ..Default::default()
+            }));
+        }
+
         let percent = completed as f64 / total as f64;
 
         let completed_str = completed.to_string();
@@ -71,6 +131,14 @@ impl M3u8Shower {
             rate = rate_str,
         );
 
+        if self.progress == ProgressFormat::Plain {
+            if plain_line_due(&mut self.last_line, completed >= total) {
+                writeln!(&mut self.stdout, "{}", info)?;
+                self.stdout.flush()?;
+            }
+            return Ok(());
+        }
+
         // set default info length
         let info_length = total_str.len() * 2 + 26;
         let mut miss = info_length - info.len();
@@ -90,14 +158,14 @@ impl M3u8Shower {
         write!(
             &mut self.stdout,
             "\r{completed}/{total} {length} {percent}% {rate}/s{miss} {bar_done}{bar_undone}  ",
-            completed = Red.bold().paint(completed_str),
-            total = Green.bold().paint(total_str),
-            length = Red.bold().paint(length_str),
-            percent = Yellow.bold().paint(percent_str),
-            rate = Blue.bold().paint(rate_str),
+            completed = styled(self.color, Red.bold()).paint(completed_str),
+            total = styled(self.color, Green.bold()).paint(total_str),
+            length = styled(self.color, Red.bold()).paint(length_str),
+            percent = styled(self.color, Yellow.bold()).paint(percent_str),
+            rate = styled(self.color, Blue.bold()).paint(rate_str),
             miss = " ".repeat(miss),
-            bar_done = Red.bold().paint(bar_done_str),
-            bar_undone = Black.bold().paint(bar_undone_str),
+            bar_done = styled(self.color, Red.bold()).paint(bar_done_str),
+            bar_undone = styled(self.color, Black.bold()).paint(bar_undone_str),
         )?;
 
         self.stdout.flush()?;
@@ -1,4 +1,6 @@
 pub mod bt_show;
 pub mod common;
+pub mod dashboard;
+pub mod event;
 pub mod http_show;
 pub mod m3u8_show;
@@ -0,0 +1,89 @@
+use crate::common::range::RangePair;
+
+/// The number of blocks of a `BlockMap`
+const BLOCKS: u64 = 320;
+
+/// `BlockMap` divides a file into blocks and records the downloaded bytes of each block
+pub struct BlockMap {
+    total: u64,
+    block_size: u64,
+    blocks: Vec<u64>,
+}
+
+impl BlockMap {
+    pub fn new(total: u64) -> BlockMap {
+        let block_size = std::cmp::max(1, total.div_ceil(BLOCKS));
+        let count = total.div_ceil(block_size);
+        BlockMap {
+            total,
+            block_size,
+            blocks: vec![0; count as usize],
+        }
+    }
+
+    /// Add a downloaded pair
+    pub fn add(&mut self, pair: RangePair) {
+        if pair.begin >= self.total {
+            return;
+        }
+        let end = std::cmp::min(pair.end, self.total - 1);
+        let mut begin = pair.begin;
+        while begin <= end {
+            let index = begin / self.block_size;
+            let block_end = std::cmp::min((index + 1) * self.block_size - 1, end);
+            self.blocks[index as usize] += block_end - begin + 1;
+            begin = block_end + 1;
+        }
+    }
+
+    fn block_length(&self, index: usize) -> u64 {
+        let begin = index as u64 * self.block_size;
+        std::cmp::min(self.block_size, self.total - begin)
+    }
+
+    /// Render the map as lines of at most `width` chars
+    ///
+    /// `#` is a completed block, `+` is a partially downloaded block and `.` is a missing block.
+    pub fn render(&self, width: usize) -> Vec<String> {
+        let chars: Vec<char> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(index, done)| {
+                if *done >= self.block_length(index) {
+                    '#'
+                } else if *done > 0 {
+                    '+'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        chars
+            .chunks(std::cmp::max(1, width))
+            .map(|line| line.iter().collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockMap;
+    use crate::common::range::RangePair;
+
+    #[test]
+    fn test_block_map() {
+        // 250 blocks of 4 bytes
+        let mut map = BlockMap::new(1000);
+        let lines = map.render(100);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], ".".repeat(50));
+
+        map.add(RangePair::new(0, 9));
+        map.add(RangePair::new(12, 14));
+        map.add(RangePair::new(995, 2000));
+        let lines = map.render(250);
+        assert_eq!(&lines[0][..5], "##++.");
+        assert_eq!(&lines[0][245..], "...+#");
+    }
+}
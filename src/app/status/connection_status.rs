@@ -0,0 +1,148 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::common::range::RangePair;
+
+#[derive(Debug, Default)]
+struct Entry {
+    range: Option<RangePair>,
+    ranges: usize,
+    length: u64,
+    done: u64,
+    received: u64,
+    retries: u64,
+}
+
+struct Inner {
+    entries: BTreeMap<u64, Entry>,
+    // Received bytes of each connection at the last snapshot
+    last: BTreeMap<u64, u64>,
+    tick: Instant,
+}
+
+/// The status of a connection at a moment
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    /// The span of the requested ranges
+    pub range: Option<RangePair>,
+    /// The number of requested ranges, which is more than 1 for multi-range requests
+    pub ranges: usize,
+    /// The length of the requested ranges
+    pub length: u64,
+    /// Received bytes of the requested ranges
+    pub done: u64,
+    /// Bytes per second since the last snapshot
+    pub rate: f64,
+    /// The number of failed requests
+    pub retries: u64,
+}
+
+/// `ConnectionStatus` records the status of each running range request task
+#[derive(Clone)]
+pub struct ConnectionStatus {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ConnectionStatus {
+    pub fn new() -> ConnectionStatus {
+        ConnectionStatus {
+            inner: Arc::new(Mutex::new(Inner {
+                entries: BTreeMap::new(),
+                last: BTreeMap::new(),
+                tick: Instant::now(),
+            })),
+        }
+    }
+
+    /// Record a new connection. The record is removed when the returned handle is dropped.
+    pub fn open(&self, id: u64) -> Connection {
+        self.inner.lock().unwrap().entries.insert(id, Entry::default());
+        Connection {
+            status: self.clone(),
+            id,
+        }
+    }
+
+    /// The status of all connections, whose rates are measured since the last snapshot
+    pub fn snapshot(&self) -> Vec<ConnectionInfo> {
+        let mut inner = self.inner.lock().unwrap();
+        let interval = inner.tick.elapsed().as_secs_f64();
+        inner.tick = Instant::now();
+
+        let Inner { entries, last, .. } = &mut *inner;
+        last.retain(|id, _| entries.contains_key(id));
+        entries
+            .iter()
+            .map(|(id, entry)| {
+                let previous = last.insert(*id, entry.received).unwrap_or(0);
+                ConnectionInfo {
+                    id: *id,
+                    range: entry.range,
+                    ranges: entry.ranges,
+                    length: entry.length,
+                    done: entry.done,
+                    rate: if interval > 0.0 {
+                        (entry.received - previous) as f64 / interval
+                    } else {
+                        0.0
+                    },
+                    retries: entry.retries,
+                }
+            })
+            .collect()
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Entry)) {
+        if let Some(entry) = self.inner.lock().unwrap().entries.get_mut(&id) {
+            f(entry);
+        }
+    }
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> ConnectionStatus {
+        ConnectionStatus::new()
+    }
+}
+
+/// The handle of a connection in the `ConnectionStatus`
+pub struct Connection {
+    status: ConnectionStatus,
+    id: u64,
+}
+
+impl Connection {
+    /// A request for the pairs starts
+    pub fn request(&self, pairs: &[RangePair]) {
+        let begin = pairs.iter().map(|pair| pair.begin).min();
+        let end = pairs.iter().map(|pair| pair.end).max();
+        self.status.update(self.id, |entry| {
+            entry.range = begin.zip(end).map(|(begin, end)| RangePair::new(begin, end));
+            entry.ranges = pairs.len();
+            entry.length = pairs.iter().map(RangePair::length).sum();
+            entry.done = 0;
+        });
+    }
+
+    pub fn add_received(&self, incr: u64) {
+        self.status.update(self.id, |entry| {
+            entry.done += incr;
+            entry.received += incr;
+        });
+    }
+
+    /// A request fails and the task goes on
+    pub fn add_retry(&self) {
+        self.status.update(self.id, |entry| entry.retries += 1);
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.status.inner.lock().unwrap().entries.remove(&self.id);
+    }
+}
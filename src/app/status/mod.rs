@@ -1 +1,4 @@
+pub mod block_map;
+pub mod connection_status;
 pub mod rate_status;
+pub mod summary;
@@ -1,4 +1,10 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// The weight of the newest rate in the smoothed rate
+const SMOOTHING: f64 = 0.3;
+
+/// Ticks shorter than it, e.g. the last one of a task, are not sampled for the smoothed and peak rates
+const MIN_SAMPLE_SECS: f64 = 1.0;
 
 /// `RateStatus` records the rate of adding number
 pub struct RateStatus {
@@ -10,6 +16,18 @@ pub struct RateStatus {
 
     /// The interval of one tick
     tick: Instant,
+
+    /// The number added since the status is created
+    added: u64,
+
+    /// The time when the status is created
+    start: Instant,
+
+    /// The exponentially weighted moving average of the rates of ticks
+    smoothed: Option<f64>,
+
+    /// The maximum rate of ticks
+    peak: f64,
 }
 
 impl RateStatus {
@@ -29,23 +47,64 @@ impl RateStatus {
         self.count
     }
 
+    /// The rate of the current tick
     pub fn rate(&self) -> f64 {
         let interval = self.tick.elapsed().as_secs_f64();
         self.count as f64 / interval
     }
 
+    /// The smoothed rate of past ticks, which is steady for estimating the remaining time
+    pub fn smoothed_rate(&self) -> f64 {
+        self.smoothed.unwrap_or_else(|| self.average_rate())
+    }
+
+    /// The average rate since the status is created
+    pub fn average_rate(&self) -> f64 {
+        let interval = self.start.elapsed().as_secs_f64();
+        if interval > 0.0 {
+            self.added as f64 / interval
+        } else {
+            0.0
+        }
+    }
+
+    /// The maximum rate of past ticks
+    pub fn peak_rate(&self) -> f64 {
+        self.peak.max(self.average_rate())
+    }
+
+    /// The number added since the status is created
+    pub fn added(&self) -> u64 {
+        self.added
+    }
+
+    /// The time since the status is created
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
     pub fn add(&mut self, incr: u64) {
         self.total += incr;
         self.count += incr;
+        self.added += incr;
     }
 
     pub fn reset(&mut self) {
-        self.total = 0;
-        self.count = 0;
-        self.tick = Instant::now();
+        *self = RateStatus::default();
     }
 
+    /// Finish the current tick and start a new one
     pub fn clean(&mut self) {
+        let interval = self.tick.elapsed().as_secs_f64();
+        if interval >= MIN_SAMPLE_SECS {
+            let rate = self.count as f64 / interval;
+            self.smoothed = Some(match self.smoothed {
+                Some(smoothed) => SMOOTHING * rate + (1.0 - SMOOTHING) * smoothed,
+                None => rate,
+            });
+            self.peak = self.peak.max(rate);
+        }
+
         self.count = 0;
         self.tick = Instant::now();
     }
@@ -57,6 +116,10 @@ impl Default for RateStatus {
             total: 0,
             count: 0,
             tick: Instant::now(),
+            added: 0,
+            start: Instant::now(),
+            smoothed: None,
+            peak: 0.0,
         }
     }
 }
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+/// The summary of a completed task
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// The final url after redirects, or the uri of the torrent
+    pub url: String,
+    /// The time the task takes, in seconds for json
+    #[serde(serialize_with = "serialize_secs")]
+    pub elapsed: Duration,
+    /// Bytes received in this run, excluding the resumed part
+    pub received: u64,
+    /// Bytes per second
+    pub average_rate: f64,
+    /// Bytes per second
+    pub peak_rate: f64,
+    /// The number of failed requests which are retried
+    pub retries: u64,
+    /// Bytes which are received but dropped, and downloaded again
+    pub redownloaded: u64,
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
@@ -1,9 +1,10 @@
 use clap::Parser;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[clap(author, version, about, long_about = None)]
 pub struct AgetCli {
-    pub url: String,
+    #[clap(required = true, help = "The urls to download. Several urls need --tui")]
+    pub url: Vec<String>,
 
     #[clap(
         short = 'X',
@@ -28,11 +29,61 @@ pub struct AgetCli {
     #[clap(short, long, help = r#"Request with POST method with the data, e.g. -d "a=b""#)]
     pub data: Option<String>,
 
+    #[clap(long, conflicts_with = "max_redirects", help = "Don't follow redirects")]
+    pub no_redirect: bool,
+
+    #[clap(long, help = "The maximum number of redirects to follow [default: 10]")]
+    pub max_redirects: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Send the Authorization and Cookie headers to other hosts after redirects. Only use it for trusted hosts"
+    )]
+    pub location_trusted: bool,
+
+    #[clap(
+        long,
+        help = "Request a compressed response and decode it. The resource is downloaded in a single stream without ranges"
+    )]
+    pub compressed: bool,
+
     #[clap(long, help = "Skip to verify the server's TLS certificate")]
     pub insecure: bool,
 
-    #[clap(short = 's', long, help = "The number of concurrency request [default: 10]")]
-    pub concurrency: Option<u64>,
+    #[clap(
+        long,
+        help = "Use HTTP/2 if the server supports it. Concurrent requests are multiplexed over HTTP/2 connections"
+    )]
+    pub http2: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "http2",
+        help = "Use HTTP/2 without negotiation, which also works for http:// urls"
+    )]
+    pub http2_prior_knowledge: bool,
+
+    #[clap(
+        long,
+        help = "The number of HTTP/2 connections which concurrent requests are multiplexed over [default: 1]"
+    )]
+    pub http2_connections: Option<u64>,
+
+    #[clap(
+        short = 's',
+        long,
+        help = "The number of concurrency request, or `auto` to tune it by the throughput [default: 10]"
+    )]
+    pub concurrency: Option<String>,
+
+    #[clap(long, help = "The maximum number of concurrency request for `-s auto` [default: 32]")]
+    pub max_concurrency: Option<u64>,
+
+    #[clap(
+        long,
+        help = "The maximum number of connections to a host, which is shared by all downloads in the process"
+    )]
+    pub max_connections_per_host: Option<u64>,
 
     #[clap(
         short = 'k',
@@ -41,6 +92,18 @@ pub struct AgetCli {
     )]
     pub chunk_size: Option<String>,
 
+    #[clap(
+        long,
+        help = "The maximum number of small ranges which are coalesced into one multi-range request. 1 disables it [default: 16]"
+    )]
+    pub max_ranges: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Limit the download speed in bytes per second, e.g. 512k, 2m. It can be changed by the keys l/L in the terminal"
+    )]
+    pub limit_rate: Option<String>,
+
     #[clap(short, long, help = "Timeout(seconds) of request [default: 60]")]
     pub timeout: Option<u64>,
 
@@ -50,11 +113,112 @@ pub struct AgetCli {
     #[clap(long, help = "The maximum times of retring [default: 5]")]
     pub retries: Option<u64>,
 
-    #[clap(long, help = "The seconds between retries [default: 0]")]
+    #[clap(
+        long,
+        help = "The seconds before the first retry, which is doubled for each retry with a random jitter [default: 1]"
+    )]
     pub retry_wait: Option<u64>,
 
-    #[clap(long = "proxy", name = "PROXY", help = "[protocol://]host[:port] Use this proxy")]
-    pub proxy: Option<String>,
+    #[clap(long, help = "The maximum seconds between retries [default: 60]")]
+    pub retry_max_wait: Option<u64>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "The response status codes which are retried, others are not [default: 408,429,500,502,503,504]"
+    )]
+    pub retry_status: Option<Vec<u16>>,
+
+    #[clap(
+        long,
+        help = "The maximum seconds of the whole task, including retries. The download is aborted after it"
+    )]
+    pub max_time: Option<u64>,
+
+    #[clap(
+        long,
+        help = "The maximum size of the file, e.g. 100m. A larger file is rejected once its length is known"
+    )]
+    pub max_filesize: Option<String>,
+
+    #[clap(
+        long,
+        help = "Abort the download if the speed is below this bytes per second for --speed-time, e.g. 10k"
+    )]
+    pub speed_limit: Option<String>,
+
+    #[clap(
+        long,
+        help = "The seconds which the speed is below --speed-limit before the download is aborted [default: 30]"
+    )]
+    pub speed_time: Option<u64>,
+
+    #[clap(
+        long = "proxy",
+        name = "PROXY",
+        help = "[protocol://]host[:port] Use this proxy. Concurrent requests are rotated across proxies if it is given multiple times"
+    )]
+    pub proxy: Option<Vec<String>>,
+
+    #[clap(
+        long,
+        help = "A file of proxies, one per line. Concurrent requests are rotated across them and failed proxies are removed"
+    )]
+    pub proxy_list: Option<String>,
+
+    #[clap(long, help = "[protocol://]host[:port] Use this proxy for http urls")]
+    pub http_proxy: Option<String>,
+
+    #[clap(long, help = "[protocol://]host[:port] Use this proxy for https urls")]
+    pub https_proxy: Option<String>,
+
+    #[clap(
+        long,
+        help = r#"<no-proxy-list> Comma-separated hosts which are not proxied, e.g. --noproxy "localhost,.example.com,10.0.0.0/8""#
+    )]
+    pub noproxy: Option<String>,
+
+    #[clap(long, help = "<user:password> User and password for proxy authentication")]
+    pub proxy_user: Option<String>,
+
+    #[clap(long, help = "A local PAC file which decides the proxy of each url")]
+    pub proxy_pac: Option<String>,
+
+    #[clap(
+        long,
+        help = "<host:port:addr[,addr]...> Resolve the host to the addresses, e.g. --resolve \"example.com:443:127.0.0.1\""
+    )]
+    pub resolve: Option<Vec<String>>,
+
+    #[clap(
+        short = '4',
+        long,
+        conflicts_with = "ipv6",
+        help = "Resolve names to IPv4 addresses only"
+    )]
+    pub ipv4: bool,
+
+    #[clap(short = '6', long, help = "Resolve names to IPv6 addresses only")]
+    pub ipv6: bool,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "<addr[:port][,addr[:port]]...> DNS servers to resolve names, e.g. --dns-servers \"1.1.1.1,8.8.8.8\""
+    )]
+    pub dns_servers: Option<Vec<String>>,
+
+    #[clap(
+        long,
+        help = "Resolve all addresses of the server once and spread concurrent requests across them. Failed addresses are avoided"
+    )]
+    pub spread_addrs: bool,
+
+    #[clap(
+        long,
+        help = r#"Connect through this unix domain socket instead of tcp, e.g. --unix-socket "/var/run/docker.sock""#
+    )]
+    pub unix_socket: Option<String>,
 
     #[clap(
         long = "type",
@@ -92,10 +256,69 @@ pub struct AgetCli {
 
     #[clap(
         long,
+        conflicts_with = "progress",
         help = "Quiet mode. Don't show progress bar and task information. But still show the error information"
     )]
     pub quiet: bool,
 
-    #[clap(short, long, help = r#"The path of output for the request e.g. -o "/path/to/file""#)]
+    #[clap(
+        long,
+        default_value = "bar",
+        help = "Progress output, bar/plain/json. `bar` turns to `plain` lines when stdout is not a terminal. `json` prints newline-delimited json events for other programs. Colors are disabled by the NO_COLOR environment variable"
+    )]
+    pub progress: String,
+
+    #[clap(
+        short,
+        long,
+        help = "Show the range, received bytes, speed and retries of each connection, and the map of downloaded blocks"
+    )]
+    pub verbose: bool,
+
+    #[clap(
+        long,
+        help = r#"Write logs to the file instead of stdout, e.g. --log-file "/path/to/aget.log""#
+    )]
+    pub log_file: Option<String>,
+
+    #[clap(
+        long,
+        help = "The maximum level of logs, error/warn/info/debug/trace. [default: error, or debug for --debug]"
+    )]
+    pub log_level: Option<String>,
+
+    #[clap(long, default_value = "text", help = "The format of logs, text/json")]
+    pub log_format: String,
+
+    #[clap(
+        long,
+        default_value = "never",
+        help = "How often the log file is rotated, never/hourly/daily/weekly. The rotated files are suffixed by the date"
+    )]
+    pub log_rotation: String,
+
+    #[clap(long, help = "The maximum number of rotated log files to keep")]
+    pub log_max_files: Option<u64>,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["quiet", "progress", "verbose"],
+        help = "Show a dashboard of the tasks of all urls. Keys: up/down select, p pause/resume, c cancel, +/- concurrency, q quit"
+    )]
+    pub tui: bool,
+
+    #[clap(
+        short,
+        long,
+        default_value_t = 3,
+        help = "The number of tasks which run at the same time for --tui"
+    )]
+    pub jobs: u64,
+
+    #[clap(
+        short,
+        long,
+        help = r#"The path of output for the request e.g. -o "/path/to/file". It is the directory of the files for several urls"#
+    )]
     pub out: Option<String>,
 }
@@ -1,49 +1,283 @@
 use std::{
     fmt,
+    io::{stdout, IsTerminal},
+    net::SocketAddr,
     path::{Path, PathBuf},
     time::Duration,
 };
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use tracing::Level;
 
 #[cfg(windows)]
 use ansi_term::enable_ansi_support;
 
-use percent_encoding::percent_decode;
-
 use crate::{
-    arguments::clap_cli::AgetCli,
+    app::show::common::ProgressFormat,
+    arguments::{
+        clap_cli::AgetCli,
+        options::{parse_method, url_file_name, url_task_type, DownloadOptions, DEFAULT_HEADERS},
+    },
     common::{
         character::escape_nonascii,
-        errors::Error,
+        errors::{Error, Result},
         liberal::ParseLiteralNumber,
-        net::{net::parse_headers, Method, Url},
+        log::{LogFormat, LogRotation},
+        net::{
+            dns::IpFamily,
+            net::{header_pair, parse_dns_server, parse_headers, parse_resolve},
+            HttpVersion, Method, Url,
+        },
         tasks::TaskType,
     },
     config::Config,
     features::args::Args,
 };
 
-const DEFAULT_HEADERS: [(&str, &str); 1] = [(
-    "user-agent",
-    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
-)];
+/// `Args` methods parse the arguments which are checked by `CmdArgs::new`
+const CHECKED: &str = "arguments are checked";
 
 pub struct CmdArgs {
     cli: AgetCli,
     config: Config,
+    proxies: Vec<String>,
 }
 
 impl CmdArgs {
-    pub fn new() -> CmdArgs {
+    pub fn new() -> Result<CmdArgs> {
         #[cfg(windows)]
         let _ = enable_ansi_support();
 
-        CmdArgs {
-            cli: AgetCli::parse(),
-            config: Config::new(),
+        let cli = AgetCli::parse();
+        if cli.url.len() > 1 && !cli.tui {
+            AgetCli::command()
+                .bin_name("ag")
+                .error(ErrorKind::TooManyValues, "several urls need --tui")
+                .exit();
+        }
+        let proxies = load_proxies(&cli)?;
+
+        let cmdargs = CmdArgs {
+            cli,
+            config: Config::new()?,
+            proxies,
+        };
+        cmdargs.check()?;
+        Ok(cmdargs)
+    }
+
+    /// Check the arguments which are parsed by `Args` methods, so that they don't panic
+    fn check(&self) -> Result<()> {
+        self.try_method()?;
+        if let Some(ref headers) = self.cli.header {
+            parse_headers(headers.iter().map(|h| h.as_str()))?;
+        }
+        for (name, value) in self.headers() {
+            header_pair(name, value)?;
+        }
+        for raw in self.cli.resolve.iter().flatten() {
+            parse_resolve(raw)?;
+        }
+        for raw in self.cli.dns_servers.iter().flatten() {
+            parse_dns_server(raw)?;
+        }
+        self.try_concurrency()?;
+        self.try_chunk_size()?;
+        self.try_limit_rate()?;
+        self.try_max_filesize()?;
+        self.try_speed_limit()?;
+        self.try_progress()?;
+        self.try_log_level()?;
+        self.try_log_format()?;
+        self.try_log_rotation()?;
+
+        // Several urls are downloaded to their file names in the output directory
+        let several = self.cli.url.len() > 1;
+        for raw in self.cli.url.iter() {
+            let url = parse_url(raw)?;
+            url_task_type(&self.cli.tp, &url)?;
+            if self.cli.out.is_none() || several {
+                url_file_name(&url)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn try_method(&self) -> Result<Method> {
+        if self.cli.data.is_some() {
+            return Ok(Method::POST);
+        }
+        parse_method(&self.cli.request)
+    }
+
+    fn try_concurrency(&self) -> Result<u64> {
+        let concurrency = match self.cli.concurrency.as_deref() {
+            // `-s auto` is checked by `Args::auto_concurrency`
+            Some("auto") => return Ok(0),
+            Some(concurrency) => concurrency.parse::<u64>()?,
+            None => self.config.concurrency.unwrap_or(10),
+        };
+        if concurrency == 0 {
+            return Err(Error::InvalidArgument("The concurrency must be at least 1".to_owned()));
+        }
+        Ok(concurrency)
+    }
+
+    /// The proxy from the environment, unless proxies are given by `--proxy` or `--proxy-list`
+    fn proxy_env(&self, names: &[&str]) -> Option<String> {
+        if self.cli.proxy.is_some() || self.cli.proxy_list.is_some() {
+            return None;
+        }
+        env_var(names)
+    }
+
+    fn try_chunk_size(&self) -> Result<u64> {
+        match self.cli.chunk_size.as_deref() {
+            Some(chunk_size) => chunk_size.literal_number(),
+            None => match self.config.chunk_size.as_deref() {
+                Some(chunk_size) => chunk_size.literal_number(),
+                None => Ok(1024 * 1024 * 50), // 50m
+            },
+        }
+    }
+
+    fn try_limit_rate(&self) -> Result<Option<u64>> {
+        self.cli.limit_rate.as_deref().map(|i| i.literal_number()).transpose()
+    }
+
+    fn try_max_filesize(&self) -> Result<Option<u64>> {
+        self.cli.max_filesize.as_deref().map(|i| i.literal_number()).transpose()
+    }
+
+    fn try_speed_limit(&self) -> Result<Option<u64>> {
+        self.cli.speed_limit.as_deref().map(|i| i.literal_number()).transpose()
+    }
+
+    fn try_progress(&self) -> Result<ProgressFormat> {
+        match self.cli.progress.to_lowercase().as_str() {
+            "bar" if stdout().is_terminal() => Ok(ProgressFormat::Bar),
+            "bar" | "plain" => Ok(ProgressFormat::Plain),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unsupported progress format: {}",
+                self.cli.progress
+            ))),
+        }
+    }
+
+    fn try_log_level(&self) -> Result<Level> {
+        match &self.cli.log_level {
+            Some(level) => level
+                .parse::<Level>()
+                .map_err(|_| Error::InvalidArgument(format!("Unsupported log level: {}", level))),
+            None => {
+                if self.debug() {
+                    Ok(Level::DEBUG)
+                } else {
+                    Ok(Level::ERROR)
+                }
+            }
+        }
+    }
+
+    fn try_log_format(&self) -> Result<LogFormat> {
+        match self.cli.log_format.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unsupported log format: {}",
+                self.cli.log_format
+            ))),
+        }
+    }
+
+    fn try_log_rotation(&self) -> Result<LogRotation> {
+        match self.cli.log_rotation.to_lowercase().as_str() {
+            "never" => Ok(LogRotation::Never),
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            "weekly" => Ok(LogRotation::Weekly),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unsupported log rotation: {}",
+                self.cli.log_rotation
+            ))),
+        }
+    }
+
+    /// The download options of the arguments
+    pub fn options(&self) -> DownloadOptions {
+        DownloadOptions::from_args(self)
+    }
+
+    /// The arguments of each url. For several urls, the output is the directory of the files.
+    ///
+    /// Files of the same name are suffixed by a number, e.g. `file.1`, so that tasks don't write
+    /// the same file.
+    pub fn tasks(&self) -> Vec<CmdArgs> {
+        let several = self.cli.url.len() > 1;
+        let mut outputs: Vec<PathBuf> = vec![];
+        self.cli
+            .url
+            .iter()
+            .map(|url| {
+                let mut args = CmdArgs {
+                    cli: self.cli.clone(),
+                    config: self.config.clone(),
+                    proxies: self.proxies.clone(),
+                };
+                args.cli.url = vec![url.clone()];
+                if several {
+                    args.cli.out = None;
+                    let name = args.output();
+                    let dir = self.cli.out.as_deref().unwrap_or("");
+                    let mut output = Path::new(dir).join(&name);
+                    let mut n = 0;
+                    while outputs.contains(&output) {
+                        n += 1;
+                        output = Path::new(dir).join(format!("{}.{}", name.to_string_lossy(), n));
+                    }
+                    args.cli.out = Some(output.to_string_lossy().into_owned());
+                    outputs.push(output);
+                }
+                args
+            })
+            .collect()
+    }
+}
+
+/// Parse the url, in which non-ascii characters are escaped
+fn parse_url(raw: &str) -> Result<Url> {
+    Ok(escape_nonascii(raw).parse()?)
+}
+
+/// The value of the first set environment variable
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Load proxies from `--proxy` and the `--proxy-list` file, or the `ALL_PROXY` environment
+/// variable if there is none of them
+///
+/// Each line of the file is a proxy. Empty lines and lines starting with `#` are ignored.
+fn load_proxies(cli: &AgetCli) -> Result<Vec<String>> {
+    let mut proxies = cli.proxy.clone().unwrap_or_default();
+    if let Some(path) = &cli.proxy_list {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| Error::InvalidArgument(format!("Can't read proxy list file {}: {}", path, err)))?;
+        for line in content.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                proxies.push(line.to_owned());
+            }
         }
     }
+    if proxies.is_empty() {
+        proxies.extend(env_var(&["ALL_PROXY", "all_proxy"]));
+    }
+    Ok(proxies)
 }
 
 impl Args for CmdArgs {
@@ -52,36 +286,18 @@ impl Args for CmdArgs {
         if let Some(path) = self.cli.out.clone() {
             PathBuf::from(path)
         } else {
-            let url = self.url();
-            let path = Path::new(url.path());
-            if let Some(file_name) = path.file_name() {
-                PathBuf::from(
-                    percent_decode(file_name.to_str().unwrap().as_bytes())
-                        .decode_utf8()
-                        .unwrap()
-                        .to_string(),
-                )
-            } else {
-                panic!("{:?}", Error::NoFilename);
-            }
+            url_file_name(&self.url()).expect(CHECKED)
         }
     }
 
     /// Request method for http
     fn method(&self) -> Method {
-        if self.cli.data.is_some() {
-            return Method::POST;
-        }
-        match self.cli.request.to_uppercase().as_str() {
-            "GET" => Method::GET,
-            "POST" => Method::POST,
-            _ => panic!("{:?}", Error::UnsupportedMethod(self.cli.request.to_string())),
-        }
+        self.try_method().expect(CHECKED)
     }
 
     /// The url of a task
     fn url(&self) -> Url {
-        escape_nonascii(&self.cli.url).parse().expect("URL is unvalidable")
+        parse_url(&self.cli.url[0]).expect(CHECKED)
     }
 
     /// The data for http post request
@@ -92,7 +308,7 @@ impl Args for CmdArgs {
     /// Request headers
     fn headers(&self) -> Vec<(&str, &str)> {
         let mut headers = if let Some(ref headers) = self.cli.header {
-            let v = parse_headers(headers.iter().map(|h| h.as_str())).unwrap();
+            let v = parse_headers(headers.iter().map(|h| h.as_str())).expect(CHECKED);
             v.into_iter().collect::<Vec<(&str, &str)>>()
         } else {
             vec![]
@@ -163,8 +379,88 @@ impl Args for CmdArgs {
     /// socks5://
     /// socks5h://
     ///        as SOCKS proxy
+    ///
+    /// If there are multiple proxies, return the first one.
     fn proxy(&self) -> Option<&str> {
-        self.cli.proxy.as_deref()
+        self.proxies.first().map(|v| v.as_str())
+    }
+
+    /// Proxy for http urls from `--http-proxy` or `http_proxy`
+    ///
+    /// As curl, the uppercase `HTTP_PROXY` is not used, and the environment is ignored when
+    /// `--proxy` or `--proxy-list` is given.
+    fn http_proxy(&self) -> Option<String> {
+        self.cli.http_proxy.clone().or_else(|| self.proxy_env(&["http_proxy"]))
+    }
+
+    /// Proxy for https urls from `--https-proxy`, `HTTPS_PROXY` or `https_proxy`
+    fn https_proxy(&self) -> Option<String> {
+        self.cli
+            .https_proxy
+            .clone()
+            .or_else(|| self.proxy_env(&["HTTPS_PROXY", "https_proxy"]))
+    }
+
+    /// Hosts which are not proxied from `--noproxy`, `NO_PROXY` or `no_proxy`
+    fn no_proxy(&self) -> Option<String> {
+        self.cli.noproxy.clone().or_else(|| env_var(&["NO_PROXY", "no_proxy"]))
+    }
+
+    /// `user:password` for proxy authentication
+    fn proxy_user(&self) -> Option<String> {
+        self.cli.proxy_user.clone()
+    }
+
+    /// The PAC file which decides the proxy of each url
+    fn proxy_pac(&self) -> Option<PathBuf> {
+        self.cli.proxy_pac.as_ref().map(PathBuf::from)
+    }
+
+    /// All proxies from `--proxy` and the `--proxy-list` file
+    fn proxies(&self) -> Vec<String> {
+        self.proxies.clone()
+    }
+
+    /// Resolve hosts to the given addresses as curl's `--resolve host:port:addr[,addr]...`
+    fn resolve(&self) -> Vec<(String, Vec<SocketAddr>)> {
+        if let Some(ref resolve) = self.cli.resolve {
+            resolve.iter().map(|raw| parse_resolve(raw).expect(CHECKED)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// The address family used to connect to hosts
+    fn ip_family(&self) -> IpFamily {
+        if self.cli.ipv4 {
+            IpFamily::V4
+        } else if self.cli.ipv6 {
+            IpFamily::V6
+        } else {
+            IpFamily::Any
+        }
+    }
+
+    /// Dns servers used to resolve hosts
+    fn dns_servers(&self) -> Vec<SocketAddr> {
+        if let Some(ref servers) = self.cli.dns_servers {
+            servers
+                .iter()
+                .map(|raw| parse_dns_server(raw).expect(CHECKED))
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Spread range requests across all addresses of the server
+    fn spread_addrs(&self) -> bool {
+        self.cli.spread_addrs
+    }
+
+    /// Connect to the server through this unix domain socket instead of tcp
+    fn unix_socket(&self) -> Option<PathBuf> {
+        self.cli.unix_socket.as_ref().map(PathBuf::from)
     }
 
     /// Set request timeout
@@ -185,7 +481,7 @@ impl Args for CmdArgs {
     }
 
     fn dns_timeout(&self) -> Duration {
-        Duration::from_secs(self.cli.dns_timeout.unwrap_or(10))
+        Duration::from_secs(self.cli.dns_timeout.or(self.config.dns_timeout).unwrap_or(10))
     }
 
     fn keep_alive(&self) -> Duration {
@@ -204,9 +500,19 @@ impl Args for CmdArgs {
         }
     }
 
-    /// Always return `true`
+    /// Don't follow redirects
     fn disable_redirects(&self) -> bool {
-        true
+        self.cli.no_redirect
+    }
+
+    /// The maximum number of redirects to follow
+    fn max_redirects(&self) -> u64 {
+        self.cli.max_redirects.unwrap_or(10)
+    }
+
+    /// Send the headers of credentials to the other origins which are redirected to
+    fn location_trusted(&self) -> bool {
+        self.cli.location_trusted
     }
 
     /// Skip to verify the server's TLS certificate
@@ -214,26 +520,73 @@ impl Args for CmdArgs {
         self.cli.insecure
     }
 
+    /// The http version used to connect to servers
+    fn http_version(&self) -> HttpVersion {
+        if self.cli.http2_prior_knowledge {
+            HttpVersion::Http2PriorKnowledge
+        } else if self.cli.http2 {
+            HttpVersion::Http2
+        } else {
+            HttpVersion::Http1
+        }
+    }
+
+    /// The number of HTTP/2 connections which concurrent requests are multiplexed over
+    fn http2_connections(&self) -> u64 {
+        self.cli.http2_connections.unwrap_or(1).max(1)
+    }
+
     /// The number of concurrency
+    ///
+    /// `-s auto` starts http tasks with 2 concurrency requests.
     fn concurrency(&self) -> u64 {
-        self.cli
-            .concurrency
-            .unwrap_or_else(|| self.config.concurrency.unwrap_or(10))
+        if self.auto_concurrency() {
+            return match self.task_type() {
+                TaskType::HTTP => std::cmp::min(2, self.max_concurrency()),
+                _ => std::cmp::min(10, self.max_concurrency()),
+            };
+        }
+        self.try_concurrency().expect(CHECKED)
+    }
+
+    /// The maximum number of connections to a host
+    fn max_connections_per_host(&self) -> Option<u64> {
+        self.cli.max_connections_per_host.map(|n| n.max(1))
+    }
+
+    /// Tune the number of concurrency by the throughput, with `-s auto`
+    fn auto_concurrency(&self) -> bool {
+        self.cli.concurrency.as_deref() == Some("auto")
+    }
+
+    /// The maximum number of concurrency for `-s auto`
+    fn max_concurrency(&self) -> u64 {
+        self.cli.max_concurrency.unwrap_or(32).max(1)
+    }
+
+    /// Choose the chunk size by the content length for `-s auto`, if the chunk size is not given
+    fn auto_chunk_size(&self) -> bool {
+        self.auto_concurrency() && self.cli.chunk_size.is_none() && self.config.chunk_size.is_none()
+    }
+
+    /// Request a compressed response and decode it
+    fn compressed(&self) -> bool {
+        self.cli.compressed
+    }
+
+    /// The maximum number of small ranges which are coalesced into one multi-range request
+    fn max_ranges(&self) -> u64 {
+        self.cli.max_ranges.unwrap_or(16).max(1)
     }
 
     /// The chunk size of each concurrency for http task
     fn chunk_size(&self) -> u64 {
-        self.cli
-            .chunk_size
-            .as_deref()
-            .map(|i| i.literal_number().unwrap())
-            .unwrap_or_else(|| {
-                self.config
-                    .chunk_size
-                    .as_ref()
-                    .map(|i| i.as_str().literal_number().unwrap())
-                    .unwrap_or(1024 * 1024 * 50)
-            }) // 50m
+        self.try_chunk_size().expect(CHECKED)
+    }
+
+    /// The speed limit in bytes per second
+    fn limit_rate(&self) -> Option<u64> {
+        self.try_limit_rate().expect(CHECKED)
     }
 
     /// The number of retry of a task, default is 5
@@ -241,33 +594,49 @@ impl Args for CmdArgs {
         self.cli.retries.unwrap_or_else(|| self.config.retries.unwrap_or(5))
     }
 
-    /// The internal of each retry, default is zero
+    /// The wait before the first retry, default is 1 second
     fn retry_wait(&self) -> u64 {
         self.cli
             .retry_wait
-            .unwrap_or_else(|| self.config.retry_wait.unwrap_or(0))
+            .unwrap_or_else(|| self.config.retry_wait.unwrap_or(1))
+    }
+
+    /// The maximum wait between retries, default is 60 seconds
+    fn retry_max_wait(&self) -> u64 {
+        self.cli.retry_max_wait.unwrap_or(60)
+    }
+
+    /// The response status codes which are retried
+    fn retry_statuses(&self) -> Vec<u16> {
+        self.cli
+            .retry_status
+            .clone()
+            .unwrap_or_else(|| vec![408, 429, 500, 502, 503, 504])
+    }
+
+    /// The maximum seconds of the whole task, including retries
+    fn max_time(&self) -> Option<u64> {
+        self.cli.max_time
+    }
+
+    /// The maximum size of the file in bytes
+    fn max_filesize(&self) -> Option<u64> {
+        self.try_max_filesize().expect(CHECKED)
+    }
+
+    /// The lowest speed in bytes per second
+    fn speed_limit(&self) -> Option<u64> {
+        self.try_speed_limit().expect(CHECKED)
+    }
+
+    /// The seconds of low speed before aborting, default is 30 seconds
+    fn speed_time(&self) -> u64 {
+        self.cli.speed_time.unwrap_or(30)
     }
 
     /// Task type
     fn task_type(&self) -> TaskType {
-        match self.cli.tp.as_str() {
-            "auto" => {
-                let url = self.url();
-                if url.scheme() == "magnet" || url.path().to_lowercase().ends_with(".torrent") {
-                    TaskType::BT
-                } else if url.path().to_lowercase().ends_with(".m3u8") {
-                    TaskType::M3U8
-                } else if url.scheme().starts_with("http") {
-                    TaskType::HTTP
-                } else {
-                    panic!("{:?}", Error::UnsupportedTask(self.cli.tp.clone()))
-                }
-            }
-            "http" => TaskType::HTTP,
-            "m3u8" => TaskType::M3U8,
-            "bt" => TaskType::BT,
-            _ => panic!("{:?}", Error::UnsupportedTask(self.cli.tp.clone())),
-        }
+        url_task_type(&self.cli.tp, &self.url()).expect(CHECKED)
     }
 
     /// A regex to only download files matching it in the torrent
@@ -309,6 +678,55 @@ impl Args for CmdArgs {
     fn quiet(&self) -> bool {
         self.cli.quiet
     }
+
+    /// The format of the progress output
+    fn progress(&self) -> ProgressFormat {
+        // The dashboard takes the terminal
+        if self.quiet() || self.tui() {
+            return ProgressFormat::Quiet;
+        }
+        self.try_progress().expect(CHECKED)
+    }
+
+    /// Show the status of each connection and the map of downloaded blocks
+    fn verbose(&self) -> bool {
+        self.cli.verbose
+    }
+
+    /// Write logs to the file instead of stdout
+    fn log_file(&self) -> Option<PathBuf> {
+        self.cli.log_file.as_ref().map(PathBuf::from)
+    }
+
+    /// The maximum level of logs. It is `DEBUG` for the debug mode, and `ERROR` by default.
+    fn log_level(&self) -> Level {
+        self.try_log_level().expect(CHECKED)
+    }
+
+    /// The format of logs
+    fn log_format(&self) -> LogFormat {
+        self.try_log_format().expect(CHECKED)
+    }
+
+    /// How often the log file is rotated
+    fn log_rotation(&self) -> LogRotation {
+        self.try_log_rotation().expect(CHECKED)
+    }
+
+    /// The maximum number of rotated log files to keep
+    fn log_max_files(&self) -> Option<u64> {
+        self.cli.log_max_files
+    }
+
+    /// Show a dashboard of tasks instead of the progress output
+    fn tui(&self) -> bool {
+        self.cli.tui
+    }
+
+    /// The number of tasks which run at the same time for the dashboard
+    fn jobs(&self) -> u64 {
+        self.cli.jobs.max(1)
+    }
 }
 
 impl fmt::Debug for CmdArgs {
@@ -320,15 +738,43 @@ impl fmt::Debug for CmdArgs {
             .field("data", &self.data())
             .field("headers", &self.headers())
             .field("proxy", &self.proxy())
+            .field("proxies", &self.proxies())
+            .field("http_proxy", &self.http_proxy())
+            .field("https_proxy", &self.https_proxy())
+            .field("no_proxy", &self.no_proxy())
+            .field("proxy_user", &self.proxy_user().map(|_| "***"))
+            .field("proxy_pac", &self.proxy_pac())
+            .field("resolve", &self.resolve())
+            .field("ip_family", &self.ip_family())
+            .field("dns_servers", &self.dns_servers())
+            .field("spread_addrs", &self.spread_addrs())
+            .field("unix_socket", &self.unix_socket())
             .field("timeout", &self.timeout())
             .field("dns_timeout", &self.dns_timeout())
             .field("keep_alive", &self.keep_alive())
             .field("lifetime", &self.lifetime())
             .field("disable_redirects", &self.disable_redirects())
+            .field("max_redirects", &self.max_redirects())
+            .field("location_trusted", &self.location_trusted())
+            .field("http_version", &self.http_version())
+            .field("http2_connections", &self.http2_connections())
             .field("concurrency", &self.concurrency())
+            .field("max_connections_per_host", &self.max_connections_per_host())
+            .field("auto_concurrency", &self.auto_concurrency())
+            .field("max_concurrency", &self.max_concurrency())
+            .field("auto_chunk_size", &self.auto_chunk_size())
+            .field("compressed", &self.compressed())
+            .field("max_ranges", &self.max_ranges())
             .field("chunk_size", &self.chunk_size())
+            .field("limit_rate", &self.limit_rate())
             .field("retries", &self.retries())
             .field("retry_wait", &self.retry_wait())
+            .field("retry_max_wait", &self.retry_max_wait())
+            .field("retry_statuses", &self.retry_statuses())
+            .field("max_time", &self.max_time())
+            .field("max_filesize", &self.max_filesize())
+            .field("speed_limit", &self.speed_limit())
+            .field("speed_time", &self.speed_time())
             .field("task_type", &self.task_type())
             .field("bt_file_regex", &self.bt_file_regex())
             .field("seed", &self.seed())
@@ -338,6 +784,15 @@ impl fmt::Debug for CmdArgs {
             .field("bt_peer_keep_alive_interval", &self.bt_peer_keep_alive_interval())
             .field("debug", &self.debug())
             .field("quiet", &self.quiet())
+            .field("progress", &self.progress())
+            .field("verbose", &self.verbose())
+            .field("log_file", &self.log_file())
+            .field("log_level", &self.log_level())
+            .field("log_format", &self.log_format())
+            .field("log_rotation", &self.log_rotation())
+            .field("log_max_files", &self.log_max_files())
+            .field("tui", &self.tui())
+            .field("jobs", &self.jobs())
             .finish()
     }
 }
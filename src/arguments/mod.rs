@@ -1,2 +1,3 @@
 pub mod clap_cli;
 pub mod cmd_args;
+pub mod options;
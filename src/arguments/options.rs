@@ -0,0 +1,967 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use percent_encoding::percent_decode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::show::common::ProgressFormat,
+    common::{
+        errors::{Error, Result},
+        net::{dns::IpFamily, net::header_pair, HttpVersion, Method, Url},
+        tasks::TaskType,
+    },
+    features::args::Args,
+};
+
+pub(crate) const DEFAULT_HEADERS: [(&str, &str); 1] = [(
+    "user-agent",
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+)];
+
+/// The owned options of a download task
+///
+/// They can be serialized, so tasks can be stored in queues or files. The proxy user is not
+/// serialized, because it has the password. Options which are not set use the defaults of `ag`,
+/// except that nothing is printed. `CmdArgs::options` builds them from the command line, and
+/// `Config::options` from the configuration file.
+///
+/// `DownloadOptions::build` checks the options and fills in the defaults which depend on the url.
+/// Options which are not built are checked when the task starts.
+///
+/// ```
+/// use aget::{common::net::Url, DownloadOptions};
+///
+/// let url: Url = "http://example.com/file.iso".parse().unwrap();
+/// let options = DownloadOptions::new(url)
+///     .with_output("/tmp/file.iso")
+///     .with_header("referer", "http://example.com")
+///     .with_concurrency(4)
+///     .with_limit_rate(1024 * 1024)
+///     .build()
+///     .unwrap();
+/// let json = serde_json::to_string(&options).unwrap();
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DownloadOptions {
+    url: Url,
+    output: Option<PathBuf>,
+    method: Option<String>,
+    data: Option<String>,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    #[serde(default)]
+    proxies: Vec<String>,
+    http_proxy: Option<String>,
+    https_proxy: Option<String>,
+    no_proxy: Option<String>,
+    #[serde(skip_serializing)]
+    proxy_user: Option<String>,
+    proxy_pac: Option<PathBuf>,
+    #[serde(default)]
+    resolve: Vec<(String, Vec<SocketAddr>)>,
+    #[serde(default)]
+    ip_family: IpFamily,
+    #[serde(default)]
+    dns_servers: Vec<SocketAddr>,
+    #[serde(default)]
+    spread_addrs: bool,
+    unix_socket: Option<PathBuf>,
+    /// Milliseconds
+    timeout_ms: Option<u64>,
+    /// Milliseconds
+    dns_timeout_ms: Option<u64>,
+    #[serde(default)]
+    disable_redirects: bool,
+    max_redirects: Option<u64>,
+    #[serde(default)]
+    location_trusted: bool,
+    #[serde(default)]
+    skip_verify_tls_cert: bool,
+    #[serde(default)]
+    http_version: HttpVersion,
+    http2_connections: Option<u64>,
+    concurrency: Option<u64>,
+    max_connections_per_host: Option<u64>,
+    #[serde(default)]
+    auto_concurrency: bool,
+    max_concurrency: Option<u64>,
+    #[serde(default)]
+    compressed: bool,
+    max_ranges: Option<u64>,
+    chunk_size: Option<u64>,
+    limit_rate: Option<u64>,
+    retries: Option<u64>,
+    retry_wait: Option<u64>,
+    retry_max_wait: Option<u64>,
+    retry_statuses: Option<Vec<u16>>,
+    max_time: Option<u64>,
+    max_filesize: Option<u64>,
+    speed_limit: Option<u64>,
+    speed_time: Option<u64>,
+    task_type: Option<TaskType>,
+    bt_file_regex: Option<String>,
+    #[serde(default)]
+    seed: bool,
+    bt_trackers: Option<Vec<String>>,
+    bt_peer_connect_timeout: Option<u64>,
+    bt_peer_read_write_timeout: Option<u64>,
+    bt_peer_keep_alive_interval: Option<u64>,
+    progress: Option<ProgressFormat>,
+    #[serde(default)]
+    verbose: bool,
+}
+
+impl DownloadOptions {
+    /// The options of the url with the defaults
+    pub fn new(url: Url) -> DownloadOptions {
+        DownloadOptions {
+            url,
+            output: None,
+            method: None,
+            data: None,
+            headers: vec![],
+            proxies: vec![],
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            proxy_user: None,
+            proxy_pac: None,
+            resolve: vec![],
+            ip_family: IpFamily::Any,
+            dns_servers: vec![],
+            spread_addrs: false,
+            unix_socket: None,
+            timeout_ms: None,
+            dns_timeout_ms: None,
+            disable_redirects: false,
+            max_redirects: None,
+            location_trusted: false,
+            skip_verify_tls_cert: false,
+            http_version: HttpVersion::Http1,
+            http2_connections: None,
+            concurrency: None,
+            max_connections_per_host: None,
+            auto_concurrency: false,
+            max_concurrency: None,
+            compressed: false,
+            max_ranges: None,
+            chunk_size: None,
+            limit_rate: None,
+            retries: None,
+            retry_wait: None,
+            retry_max_wait: None,
+            retry_statuses: None,
+            max_time: None,
+            max_filesize: None,
+            speed_limit: None,
+            speed_time: None,
+            task_type: None,
+            bt_file_regex: None,
+            seed: false,
+            bt_trackers: None,
+            bt_peer_connect_timeout: None,
+            bt_peer_read_write_timeout: None,
+            bt_peer_keep_alive_interval: None,
+            progress: None,
+            verbose: false,
+        }
+    }
+
+    /// The options of the arguments, which are checked
+    pub fn from_args(args: &impl Args) -> DownloadOptions {
+        DownloadOptions {
+            url: args.url(),
+            output: Some(args.output()),
+            method: Some(args.method().to_string()),
+            data: args.data().map(|data| data.to_owned()),
+            headers: args
+                .headers()
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+            proxies: args.proxies(),
+            http_proxy: args.http_proxy(),
+            https_proxy: args.https_proxy(),
+            no_proxy: args.no_proxy(),
+            proxy_user: args.proxy_user(),
+            proxy_pac: args.proxy_pac(),
+            resolve: args.resolve(),
+            ip_family: args.ip_family(),
+            dns_servers: args.dns_servers(),
+            spread_addrs: args.spread_addrs(),
+            unix_socket: args.unix_socket(),
+            timeout_ms: Some(args.timeout().as_millis() as u64),
+            dns_timeout_ms: Some(args.dns_timeout().as_millis() as u64),
+            disable_redirects: args.disable_redirects(),
+            max_redirects: Some(args.max_redirects()),
+            location_trusted: args.location_trusted(),
+            skip_verify_tls_cert: args.skip_verify_tls_cert(),
+            http_version: args.http_version(),
+            http2_connections: Some(args.http2_connections()),
+            concurrency: Some(args.concurrency()),
+            max_connections_per_host: args.max_connections_per_host(),
+            auto_concurrency: args.auto_concurrency(),
+            max_concurrency: Some(args.max_concurrency()),
+            compressed: args.compressed(),
+            max_ranges: Some(args.max_ranges()),
+            // The chunk size is chosen by the content length
+            chunk_size: if args.auto_chunk_size() {
+                None
+            } else {
+                Some(args.chunk_size())
+            },
+            limit_rate: args.limit_rate(),
+            retries: Some(args.retries()),
+            retry_wait: Some(args.retry_wait()),
+            retry_max_wait: Some(args.retry_max_wait()),
+            retry_statuses: Some(args.retry_statuses()),
+            max_time: args.max_time(),
+            max_filesize: args.max_filesize(),
+            speed_limit: args.speed_limit(),
+            speed_time: Some(args.speed_time()),
+            task_type: Some(args.task_type()),
+            bt_file_regex: args.bt_file_regex(),
+            seed: args.seed(),
+            bt_trackers: args.bt_trackers(),
+            bt_peer_connect_timeout: args.bt_peer_connect_timeout(),
+            bt_peer_read_write_timeout: args.bt_peer_read_write_timeout(),
+            bt_peer_keep_alive_interval: args.bt_peer_keep_alive_interval(),
+            progress: Some(args.progress()),
+            verbose: args.verbose(),
+        }
+    }
+
+    /// Check the options, and fill in the output and the task type by the url
+    pub fn build(mut self) -> Result<DownloadOptions> {
+        self.check()?;
+        if self.output.is_none() {
+            self.output = Some(url_file_name(&self.url)?);
+        }
+        if self.task_type.is_none() {
+            self.task_type = Some(url_task_type("auto", &self.url)?);
+        }
+        Ok(self)
+    }
+
+    /// Check the options which the getters replace by the defaults if they are invalid
+    pub(crate) fn check(&self) -> Result<()> {
+        if let Some(ref method) = self.method {
+            parse_method(method)?;
+        }
+        if self.task_type.is_none() {
+            url_task_type("auto", &self.url)?;
+        }
+        if self.output.is_none() {
+            url_file_name(&self.url)?;
+        }
+        for (name, value) in self.headers.iter() {
+            header_pair(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Path of output, default is the file name of the url. It is empty if the url has no file name
+    /// and the options are not built.
+    pub fn output(&self) -> PathBuf {
+        match self.output {
+            Some(ref output) => output.clone(),
+            None => url_file_name(&self.url).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_output(mut self, output: impl Into<PathBuf>) -> DownloadOptions {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// Request method for http, default is `GET`. It is `POST` if there is data. An unsupported
+    /// method of options which are not built is `GET`.
+    pub fn method(&self) -> Method {
+        if self.data.is_some() {
+            return Method::POST;
+        }
+        match self.method {
+            Some(ref method) => parse_method(method).unwrap_or(Method::GET),
+            None => Method::GET,
+        }
+    }
+
+    pub fn with_method(mut self, method: Method) -> DownloadOptions {
+        self.method = Some(method.to_string());
+        self
+    }
+
+    /// The url of a task
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// The data for http post request
+    pub fn data(&self) -> Option<&str> {
+        self.data.as_deref()
+    }
+
+    pub fn with_data(mut self, data: impl Into<String>) -> DownloadOptions {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Request headers, with the default `user-agent`
+    pub fn headers(&self) -> Vec<(&str, &str)> {
+        let mut headers: Vec<(&str, &str)> = self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        for (dk, dv) in DEFAULT_HEADERS {
+            if !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case(dk)) {
+                headers.push((dk, dv));
+            }
+        }
+        headers
+    }
+
+    /// Set a request header, which replaces the header of the same name
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> DownloadOptions {
+        let name = name.into();
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(&name));
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// The first proxy
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxies.first().map(|v| v.as_str())
+    }
+
+    /// All proxies which concurrent requests are rotated across
+    pub fn proxies(&self) -> Vec<String> {
+        self.proxies.clone()
+    }
+
+    /// Add a proxy, `[protocol://]host[:port]`
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> DownloadOptions {
+        self.proxies.push(proxy.into());
+        self
+    }
+
+    /// Proxy for http urls, which takes precedence over `proxy`
+    pub fn http_proxy(&self) -> Option<String> {
+        self.http_proxy.clone()
+    }
+
+    pub fn with_http_proxy(mut self, proxy: impl Into<String>) -> DownloadOptions {
+        self.http_proxy = Some(proxy.into());
+        self
+    }
+
+    /// Proxy for https urls, which takes precedence over `proxy`
+    pub fn https_proxy(&self) -> Option<String> {
+        self.https_proxy.clone()
+    }
+
+    pub fn with_https_proxy(mut self, proxy: impl Into<String>) -> DownloadOptions {
+        self.https_proxy = Some(proxy.into());
+        self
+    }
+
+    /// Comma-separated hosts, domains and ip ranges which are not proxied
+    pub fn no_proxy(&self) -> Option<String> {
+        self.no_proxy.clone()
+    }
+
+    pub fn with_no_proxy(mut self, no_proxy: impl Into<String>) -> DownloadOptions {
+        self.no_proxy = Some(no_proxy.into());
+        self
+    }
+
+    /// `user:password` for proxy authentication
+    pub fn proxy_user(&self) -> Option<String> {
+        self.proxy_user.clone()
+    }
+
+    pub fn with_proxy_user(mut self, proxy_user: impl Into<String>) -> DownloadOptions {
+        self.proxy_user = Some(proxy_user.into());
+        self
+    }
+
+    /// The PAC file which decides the proxy of each url
+    pub fn proxy_pac(&self) -> Option<PathBuf> {
+        self.proxy_pac.clone()
+    }
+
+    pub fn with_proxy_pac(mut self, path: impl Into<PathBuf>) -> DownloadOptions {
+        self.proxy_pac = Some(path.into());
+        self
+    }
+
+    /// Resolve hosts to the given addresses instead of using dns
+    pub fn resolve(&self) -> Vec<(String, Vec<SocketAddr>)> {
+        self.resolve.clone()
+    }
+
+    pub fn with_resolve(mut self, host: impl Into<String>, addrs: Vec<SocketAddr>) -> DownloadOptions {
+        self.resolve.push((host.into(), addrs));
+        self
+    }
+
+    /// The address family used to connect to hosts
+    pub fn ip_family(&self) -> IpFamily {
+        self.ip_family
+    }
+
+    pub fn with_ip_family(mut self, ip_family: IpFamily) -> DownloadOptions {
+        self.ip_family = ip_family;
+        self
+    }
+
+    /// Dns servers used to resolve hosts. Use the system resolver if it is empty.
+    pub fn dns_servers(&self) -> Vec<SocketAddr> {
+        self.dns_servers.clone()
+    }
+
+    pub fn with_dns_server(mut self, server: SocketAddr) -> DownloadOptions {
+        self.dns_servers.push(server);
+        self
+    }
+
+    /// Spread range requests across all addresses of the server
+    pub fn spread_addrs(&self) -> bool {
+        self.spread_addrs
+    }
+
+    pub fn with_spread_addrs(mut self, spread_addrs: bool) -> DownloadOptions {
+        self.spread_addrs = spread_addrs;
+        self
+    }
+
+    /// Connect to the server through this unix domain socket instead of tcp
+    pub fn unix_socket(&self) -> Option<PathBuf> {
+        self.unix_socket.clone()
+    }
+
+    pub fn with_unix_socket(mut self, path: impl Into<PathBuf>) -> DownloadOptions {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// The time to wait for the next data, default is 30 seconds for m3u8 tasks and 60 seconds for
+    /// others
+    pub fn timeout(&self) -> Duration {
+        let timeout = self.timeout_ms.unwrap_or(match self.task_type() {
+            TaskType::M3U8 => 30_000,
+            TaskType::HTTP | TaskType::BT => 60_000,
+        });
+        Duration::from_millis(timeout)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> DownloadOptions {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Connection timeout, including dns name resolution, default is 10 seconds
+    pub fn dns_timeout(&self) -> Duration {
+        Duration::from_millis(self.dns_timeout_ms.unwrap_or(10_000))
+    }
+
+    pub fn with_dns_timeout(mut self, timeout: Duration) -> DownloadOptions {
+        self.dns_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// The keep-alive period of connections
+    pub fn keep_alive(&self) -> Duration {
+        match self.task_type() {
+            TaskType::M3U8 => Duration::from_secs(10),
+            TaskType::HTTP | TaskType::BT => Duration::from_secs(60),
+        }
+    }
+
+    /// Don't follow redirects
+    pub fn disable_redirects(&self) -> bool {
+        self.disable_redirects
+    }
+
+    pub fn with_disable_redirects(mut self, disable_redirects: bool) -> DownloadOptions {
+        self.disable_redirects = disable_redirects;
+        self
+    }
+
+    /// The maximum number of redirects to follow, default is 10
+    pub fn max_redirects(&self) -> u64 {
+        self.max_redirects.unwrap_or(10)
+    }
+
+    pub fn with_max_redirects(mut self, max_redirects: u64) -> DownloadOptions {
+        self.max_redirects = Some(max_redirects);
+        self
+    }
+
+    /// Send the headers of credentials to the other origins which are redirected to
+    pub fn location_trusted(&self) -> bool {
+        self.location_trusted
+    }
+
+    pub fn with_location_trusted(mut self, location_trusted: bool) -> DownloadOptions {
+        self.location_trusted = location_trusted;
+        self
+    }
+
+    /// Skip to verify the server's TLS certificate
+    pub fn skip_verify_tls_cert(&self) -> bool {
+        self.skip_verify_tls_cert
+    }
+
+    pub fn with_skip_verify_tls_cert(mut self, skip: bool) -> DownloadOptions {
+        self.skip_verify_tls_cert = skip;
+        self
+    }
+
+    /// The http version used to connect to servers
+    pub fn http_version(&self) -> HttpVersion {
+        self.http_version
+    }
+
+    pub fn with_http_version(mut self, http_version: HttpVersion) -> DownloadOptions {
+        self.http_version = http_version;
+        self
+    }
+
+    /// The number of HTTP/2 connections which concurrent requests are multiplexed over, default is 1
+    pub fn http2_connections(&self) -> u64 {
+        self.http2_connections.unwrap_or(1).max(1)
+    }
+
+    pub fn with_http2_connections(mut self, connections: u64) -> DownloadOptions {
+        self.http2_connections = Some(connections);
+        self
+    }
+
+    /// The number of concurrency, default is 10
+    ///
+    /// For `auto_concurrency`, it is the initial number, 2 for http tasks and 10 for others.
+    pub fn concurrency(&self) -> u64 {
+        if self.auto_concurrency {
+            return match self.task_type() {
+                TaskType::HTTP => std::cmp::min(2, self.max_concurrency()),
+                TaskType::M3U8 | TaskType::BT => std::cmp::min(10, self.max_concurrency()),
+            };
+        }
+        self.concurrency.unwrap_or(10).max(1)
+    }
+
+    pub fn with_concurrency(mut self, concurrency: u64) -> DownloadOptions {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// The maximum number of connections to a host, which is shared by all tasks in the process
+    pub fn max_connections_per_host(&self) -> Option<u64> {
+        self.max_connections_per_host.map(|n| n.max(1))
+    }
+
+    pub fn with_max_connections_per_host(mut self, connections: u64) -> DownloadOptions {
+        self.max_connections_per_host = Some(connections);
+        self
+    }
+
+    /// Tune the number of concurrency by the throughput
+    pub fn auto_concurrency(&self) -> bool {
+        self.auto_concurrency
+    }
+
+    pub fn with_auto_concurrency(mut self, auto_concurrency: bool) -> DownloadOptions {
+        self.auto_concurrency = auto_concurrency;
+        self
+    }
+
+    /// The maximum number of concurrency for `auto_concurrency`, default is 32
+    pub fn max_concurrency(&self) -> u64 {
+        self.max_concurrency.unwrap_or(32).max(1)
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: u64) -> DownloadOptions {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    /// Choose the chunk size by the content length for `auto_concurrency`, if the chunk size is not
+    /// given
+    pub fn auto_chunk_size(&self) -> bool {
+        self.auto_concurrency && self.chunk_size.is_none()
+    }
+
+    /// Request a compressed response and decode it. The resource is downloaded in a single stream.
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+
+    pub fn with_compressed(mut self, compressed: bool) -> DownloadOptions {
+        self.compressed = compressed;
+        self
+    }
+
+    /// The maximum number of small ranges which are coalesced into one multi-range request, default
+    /// is 16
+    pub fn max_ranges(&self) -> u64 {
+        self.max_ranges.unwrap_or(16).max(1)
+    }
+
+    pub fn with_max_ranges(mut self, max_ranges: u64) -> DownloadOptions {
+        self.max_ranges = Some(max_ranges);
+        self
+    }
+
+    /// The chunk size of each concurrency for http task, default is 50m
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size.unwrap_or(1024 * 1024 * 50)
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> DownloadOptions {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// The speed limit in bytes per second
+    pub fn limit_rate(&self) -> Option<u64> {
+        self.limit_rate
+    }
+
+    pub fn with_limit_rate(mut self, limit_rate: u64) -> DownloadOptions {
+        self.limit_rate = Some(limit_rate);
+        self
+    }
+
+    /// The number of retry of a task, default is 5
+    pub fn retries(&self) -> u64 {
+        self.retries.unwrap_or(5)
+    }
+
+    pub fn with_retries(mut self, retries: u64) -> DownloadOptions {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// The seconds before the first retry, which is doubled for each retry, default is 1 second
+    pub fn retry_wait(&self) -> u64 {
+        self.retry_wait.unwrap_or(1)
+    }
+
+    pub fn with_retry_wait(mut self, secs: u64) -> DownloadOptions {
+        self.retry_wait = Some(secs);
+        self
+    }
+
+    /// The maximum seconds between retries, default is 60 seconds
+    pub fn retry_max_wait(&self) -> u64 {
+        self.retry_max_wait.unwrap_or(60)
+    }
+
+    pub fn with_retry_max_wait(mut self, secs: u64) -> DownloadOptions {
+        self.retry_max_wait = Some(secs);
+        self
+    }
+
+    /// The response status codes which are retried, default is 408, 429, 500, 502, 503 and 504
+    pub fn retry_statuses(&self) -> Vec<u16> {
+        self.retry_statuses
+            .clone()
+            .unwrap_or_else(|| vec![408, 429, 500, 502, 503, 504])
+    }
+
+    pub fn with_retry_statuses(mut self, statuses: Vec<u16>) -> DownloadOptions {
+        self.retry_statuses = Some(statuses);
+        self
+    }
+
+    /// The maximum seconds of the whole task, including retries
+    pub fn max_time(&self) -> Option<u64> {
+        self.max_time
+    }
+
+    pub fn with_max_time(mut self, secs: u64) -> DownloadOptions {
+        self.max_time = Some(secs);
+        self
+    }
+
+    /// The maximum size of the file in bytes
+    pub fn max_filesize(&self) -> Option<u64> {
+        self.max_filesize
+    }
+
+    pub fn with_max_filesize(mut self, max_filesize: u64) -> DownloadOptions {
+        self.max_filesize = Some(max_filesize);
+        self
+    }
+
+    /// The lowest speed in bytes per second, below which the download is aborted after `speed_time`
+    pub fn speed_limit(&self) -> Option<u64> {
+        self.speed_limit
+    }
+
+    pub fn with_speed_limit(mut self, speed_limit: u64) -> DownloadOptions {
+        self.speed_limit = Some(speed_limit);
+        self
+    }
+
+    /// The seconds that the speed is below `speed_limit` before the download is aborted, default is
+    /// 30 seconds
+    pub fn speed_time(&self) -> u64 {
+        self.speed_time.unwrap_or(30)
+    }
+
+    pub fn with_speed_time(mut self, secs: u64) -> DownloadOptions {
+        self.speed_time = Some(secs);
+        self
+    }
+
+    /// Task type, default is detected by the url. An unsupported url of options which are not built
+    /// is a http task.
+    pub fn task_type(&self) -> TaskType {
+        match self.task_type {
+            Some(ref task_type) => task_type.clone(),
+            None => url_task_type("auto", &self.url).unwrap_or(TaskType::HTTP),
+        }
+    }
+
+    pub fn with_task_type(mut self, task_type: TaskType) -> DownloadOptions {
+        self.task_type = Some(task_type);
+        self
+    }
+
+    /// A regex to only download files matching it in the torrent
+    pub fn bt_file_regex(&self) -> Option<String> {
+        self.bt_file_regex.clone()
+    }
+
+    pub fn with_bt_file_regex(mut self, regex: impl Into<String>) -> DownloadOptions {
+        self.bt_file_regex = Some(regex.into());
+        self
+    }
+
+    /// Seed the torrent
+    pub fn seed(&self) -> bool {
+        self.seed
+    }
+
+    pub fn with_seed(mut self, seed: bool) -> DownloadOptions {
+        self.seed = seed;
+        self
+    }
+
+    /// Trackers for the torrent
+    pub fn bt_trackers(&self) -> Option<Vec<String>> {
+        self.bt_trackers.clone()
+    }
+
+    pub fn with_bt_trackers(mut self, trackers: Vec<String>) -> DownloadOptions {
+        self.bt_trackers = Some(trackers);
+        self
+    }
+
+    /// Peer connect timeout
+    pub fn bt_peer_connect_timeout(&self) -> Option<u64> {
+        self.bt_peer_connect_timeout
+    }
+
+    pub fn with_bt_peer_connect_timeout(mut self, secs: u64) -> DownloadOptions {
+        self.bt_peer_connect_timeout = Some(secs);
+        self
+    }
+
+    /// Peer read/write timeout
+    pub fn bt_peer_read_write_timeout(&self) -> Option<u64> {
+        self.bt_peer_read_write_timeout
+    }
+
+    pub fn with_bt_peer_read_write_timeout(mut self, secs: u64) -> DownloadOptions {
+        self.bt_peer_read_write_timeout = Some(secs);
+        self
+    }
+
+    /// Peer keep alive interval
+    pub fn bt_peer_keep_alive_interval(&self) -> Option<u64> {
+        self.bt_peer_keep_alive_interval
+    }
+
+    pub fn with_bt_peer_keep_alive_interval(mut self, secs: u64) -> DownloadOptions {
+        self.bt_peer_keep_alive_interval = Some(secs);
+        self
+    }
+
+    /// The format of the progress output, default is quiet
+    pub fn progress(&self) -> ProgressFormat {
+        self.progress.unwrap_or(ProgressFormat::Quiet)
+    }
+
+    pub fn with_progress(mut self, progress: ProgressFormat) -> DownloadOptions {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Show the status of each connection and the map of downloaded blocks
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> DownloadOptions {
+        self.verbose = verbose;
+        self
+    }
+}
+
+impl fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The password of the proxy user is not shown
+        let proxy_user = self
+            .proxy_user
+            .as_deref()
+            .map(|user| format!("{}:***", user.split(':').next().unwrap_or("")));
+        f.debug_struct("DownloadOptions")
+            .field("url", &self.url.as_str())
+            .field("output", &self.output)
+            .field("method", &self.method)
+            .field("data", &self.data)
+            .field("headers", &self.headers)
+            .field("proxies", &self.proxies)
+            .field("http_proxy", &self.http_proxy)
+            .field("https_proxy", &self.https_proxy)
+            .field("no_proxy", &self.no_proxy)
+            .field("proxy_user", &proxy_user)
+            .field("proxy_pac", &self.proxy_pac)
+            .field("resolve", &self.resolve)
+            .field("ip_family", &self.ip_family)
+            .field("dns_servers", &self.dns_servers)
+            .field("spread_addrs", &self.spread_addrs)
+            .field("unix_socket", &self.unix_socket)
+            .field("timeout", &self.timeout())
+            .field("dns_timeout", &self.dns_timeout())
+            .field("disable_redirects", &self.disable_redirects)
+            .field("max_redirects", &self.max_redirects)
+            .field("location_trusted", &self.location_trusted)
+            .field("skip_verify_tls_cert", &self.skip_verify_tls_cert)
+            .field("http_version", &self.http_version)
+            .field("http2_connections", &self.http2_connections)
+            .field("concurrency", &self.concurrency)
+            .field("max_connections_per_host", &self.max_connections_per_host)
+            .field("auto_concurrency", &self.auto_concurrency)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("compressed", &self.compressed)
+            .field("max_ranges", &self.max_ranges)
+            .field("chunk_size", &self.chunk_size)
+            .field("limit_rate", &self.limit_rate)
+            .field("retries", &self.retries)
+            .field("retry_wait", &self.retry_wait)
+            .field("retry_max_wait", &self.retry_max_wait)
+            .field("retry_statuses", &self.retry_statuses)
+            .field("max_time", &self.max_time)
+            .field("max_filesize", &self.max_filesize)
+            .field("speed_limit", &self.speed_limit)
+            .field("speed_time", &self.speed_time)
+            .field("task_type", &self.task_type)
+            .field("bt_file_regex", &self.bt_file_regex)
+            .field("seed", &self.seed)
+            .field("bt_trackers", &self.bt_trackers)
+            .field("bt_peer_connect_timeout", &self.bt_peer_connect_timeout)
+            .field("bt_peer_read_write_timeout", &self.bt_peer_read_write_timeout)
+            .field("bt_peer_keep_alive_interval", &self.bt_peer_keep_alive_interval)
+            .field("progress", &self.progress)
+            .field("verbose", &self.verbose)
+            .finish()
+    }
+}
+
+/// Http methods which are supported
+pub(crate) fn parse_method(method: &str) -> Result<Method> {
+    match method.to_uppercase().as_str() {
+        "GET" => Ok(Method::GET),
+        "POST" => Ok(Method::POST),
+        _ => Err(Error::UnsupportedMethod(method.to_string())),
+    }
+}
+
+/// The task type of the url for `--type`
+pub(crate) fn url_task_type(tp: &str, url: &Url) -> Result<TaskType> {
+    match tp {
+        "auto" => {
+            if url.scheme() == "magnet" || url.path().to_lowercase().ends_with(".torrent") {
+                Ok(TaskType::BT)
+            } else if url.path().to_lowercase().ends_with(".m3u8") {
+                Ok(TaskType::M3U8)
+            } else if url.scheme().starts_with("http") {
+                Ok(TaskType::HTTP)
+            } else {
+                Err(Error::UnsupportedTask(url.scheme().to_owned()))
+            }
+        }
+        "http" => Ok(TaskType::HTTP),
+        "m3u8" => Ok(TaskType::M3U8),
+        "bt" => Ok(TaskType::BT),
+        _ => Err(Error::UnsupportedTask(tp.to_owned())),
+    }
+}
+
+/// The decoded file name of the url path
+pub(crate) fn url_file_name(url: &Url) -> Result<PathBuf> {
+    let path = Path::new(url.path());
+    match path.file_name() {
+        Some(file_name) => Ok(PathBuf::from(
+            percent_decode(file_name.to_string_lossy().as_bytes())
+                .decode_utf8_lossy()
+                .to_string(),
+        )),
+        None => Err(Error::NoFilename),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::DownloadOptions;
+    use crate::common::{net::Method, tasks::TaskType};
+
+    #[test]
+    fn test_download_options() {
+        let options = DownloadOptions::new("http://example.com/a/b.m3u8".parse().unwrap())
+            .with_header("User-Agent", "test")
+            .with_data("x=1")
+            .build()
+            .unwrap();
+        assert_eq!(options.output(), PathBuf::from("b.m3u8"));
+        assert!(matches!(options.task_type(), TaskType::M3U8));
+        assert_eq!(options.method(), Method::POST);
+        assert_eq!(options.headers(), vec![("User-Agent", "test")]);
+        assert_eq!(options.timeout().as_secs(), 30);
+
+        // Timeouts keep their milliseconds
+        let options = options
+            .with_timeout(Duration::from_millis(500))
+            .with_dns_timeout(Duration::from_millis(1500));
+        assert_eq!(options.timeout(), Duration::from_millis(500));
+        assert_eq!(options.dns_timeout(), Duration::from_millis(1500));
+
+        // Options which are not set use the defaults
+        let json = serde_json::to_string(&options).unwrap();
+        let options: DownloadOptions =
+            serde_json::from_str(r#"{"url":"http://example.com/","output":"index.html","retries":2}"#).unwrap();
+        let options = options.build().unwrap();
+        assert_eq!(options.retries(), 2);
+        assert_eq!(options.concurrency(), 10);
+        assert!(json.contains(r#""data":"x=1""#));
+
+        // Options which are not built don't panic, and the password is neither shown nor serialized
+        let options = DownloadOptions::new("ftp://example.com/".parse().unwrap()).with_proxy_user("user:password");
+        assert_eq!(options.output(), PathBuf::new());
+        assert!(matches!(options.task_type(), TaskType::HTTP));
+        assert!(!format!("{:?}", options).contains("password"));
+        assert!(!serde_json::to_string(&options).unwrap().contains("password"));
+        assert!(options.build().is_err());
+        let options: DownloadOptions =
+            serde_json::from_str(r#"{"url":"http://example.com/a","method":"PUT"}"#).unwrap();
+        assert_eq!(options.method(), Method::GET);
+        assert!(options.build().is_err());
+    }
+}
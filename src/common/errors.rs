@@ -31,6 +31,14 @@ pub enum Error {
     Io(#[from] IoError),
     #[error("{0} task is not supported")]
     UnsupportedTask(String),
+    #[error("Log file is invalid: {0}")]
+    InvalidLogFile(String),
+    #[error("--tui needs a terminal")]
+    NoTerminal,
+    #[error("Argument is invalid: {0}")]
+    InvalidArgument(String),
+    #[error("Configuration file is invalid: {0}")]
+    InvalidConfig(String),
 
     // For IO
     #[error("IO: Unexpected EOF")]
@@ -50,12 +58,26 @@ pub enum Error {
     UnsupportedMethod(String),
     #[error("header is invalid: {0}")]
     HeaderParseError(String),
-    #[error("header is invalid: {0}")]
+    #[error("Url is invalid: {0}")]
     UrlParseError(#[from] UrlParseError),
+    #[error("Url of the response is invalid: {0}")]
+    InvalidResponseUrl(String),
     #[error("BUG: {0}")]
     Bug(String),
     #[error("The two content lengths are not equal between the response and the aget file.")]
     ContentLengthIsNotConsistent,
+    #[error("Address is invalid: {0}")]
+    InvalidAddress(String),
+    #[error("DNS error: {0}")]
+    DnsError(String),
+    #[error("Proxy user is invalid: {0}")]
+    InvalidProxyUser(String),
+    #[error("PAC error: {0}")]
+    PacError(String),
+    #[error("Multi-range response is invalid: {0}")]
+    InvalidByteRanges(String),
+    #[error("The range response is encoded by `{0}`, which can't be written at its offset. Try `--compressed`")]
+    EncodedRangeResponse(String),
 
     // For m3u8
     #[error("Fail to parse m3u8 file.")]
@@ -69,6 +91,8 @@ pub enum Error {
 
     #[error("An internal error: {0}")]
     InnerError(String),
+    #[error("The task is cancelled")]
+    Cancelled,
     #[error("Content does not has length")]
     NoContentLength,
     #[error("header is invalid: {0}")]
@@ -79,8 +103,95 @@ pub enum Error {
     Redirect(String),
     #[error("No Location for redirection: {0}")]
     NoLocation(String),
+    #[error("Too many redirects, the maximum is {0}")]
+    TooManyRedirects(u64),
     #[error("Fail to decrypt aes128 data: {0}")]
     AES128DecryptFail(PadError),
+
+    // For limits of downloads
+    #[error("The file size {0} is larger than the maximum {1}")]
+    FileTooLarge(u64, u64),
+    #[error("The speed is below {0} bytes per second for {1} seconds")]
+    TooSlow(u64, u64),
+    #[error("The task exceeds the maximum time of {0} seconds")]
+    MaxTimeExceeded(u64),
+}
+
+/// Exit codes of the process for the classes of errors, like wget's
+pub mod exit_code {
+    /// Other errors, or the task is stopped by the user
+    pub const FAILURE: i32 = 1;
+    /// The arguments or the configuration file are invalid, so retrying doesn't help
+    pub const USAGE: i32 = 2;
+    /// Files can't be read or written
+    pub const DISK: i32 = 3;
+    /// Connection, dns, timeout, proxy or protocol errors, which can succeed later
+    pub const NETWORK: i32 = 4;
+    /// The server responds a 4xx status, e.g. the url is wrong
+    pub const HTTP_CLIENT: i32 = 5;
+    /// The server responds a 5xx status, which can succeed later
+    pub const HTTP_SERVER: i32 = 6;
+    /// The content doesn't match the record or can't be decrypted
+    pub const CHECKSUM: i32 = 7;
+    /// The BitTorrent session fails
+    pub const BT: i32 = 8;
+    /// The download exceeds `--max-filesize`, `--speed-limit` or `--max-time`
+    pub const LIMIT: i32 = 9;
+}
+
+impl Error {
+    /// The exit code of the process which fails by the error
+    pub fn exit_code(&self) -> i32 {
+        use exit_code::*;
+
+        // Status errors of the server
+        let status = |code: u16| match code {
+            400..=499 => HTTP_CLIENT,
+            500..=599 => HTTP_SERVER,
+            _ => NETWORK,
+        };
+        match self {
+            Error::InvalidPath(_)
+            | Error::InvaildUri(_)
+            | Error::InvalidHeader(_)
+            | Error::NoFilename
+            | Error::IsNotNumber(_)
+            | Error::UnsupportedTask(_)
+            | Error::InvalidLogFile(_)
+            | Error::NoTerminal
+            | Error::InvalidArgument(_)
+            | Error::InvalidConfig(_)
+            | Error::UnsupportedMethod(_)
+            | Error::HeaderParseError(_)
+            | Error::UrlParseError(_)
+            | Error::InvalidAddress(_)
+            | Error::InvalidProxyUser(_)
+            | Error::PacError(_) => USAGE,
+            Error::NotFoundDirectory | Error::FileExists | Error::PathIsDirectory | Error::Io(_) => DISK,
+            Error::Unsuccess(code) => status(*code),
+            Error::RequestError(err) => err.status().map_or(NETWORK, |code| status(code.as_u16())),
+            Error::UnexpectedEof
+            | Error::Timeout
+            | Error::NetError(_)
+            | Error::UncompletedRead
+            | Error::DnsError(_)
+            | Error::InvalidResponseUrl(_)
+            | Error::InvalidByteRanges(_)
+            | Error::EncodedRangeResponse(_)
+            | Error::M3U8ParseFail
+            | Error::NoContentLength
+            | Error::InvaildHeader(_)
+            | Error::Redirect(_)
+            | Error::NoLocation(_)
+            | Error::TooManyRedirects(_) => NETWORK,
+            Error::ContentLengthIsNotConsistent | Error::PartsAreNotConsistent | Error::AES128DecryptFail(_) => {
+                CHECKSUM
+            }
+            Error::BitTorrentError(_) => BT,
+            Error::FileTooLarge(..) | Error::TooSlow(..) | Error::MaxTimeExceeded(_) => LIMIT,
+            Error::Bug(_) | Error::InnerError(_) | Error::Cancelled => FAILURE,
+        }
+    }
 }
 
 impl From<http::header::ToStrError> for Error {
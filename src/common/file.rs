@@ -83,7 +83,9 @@ impl File {
         if let Some(seek) = seek {
             self.seek(seek)?;
         }
-        Ok(self.file()?.write(buf)?)
+        // A record must not be half-written
+        self.file()?.write_all(buf)?;
+        Ok(buf.len())
     }
 
     pub fn read(&mut self, buf: &mut [u8], seek: Option<SeekFrom>) -> Result<usize> {
@@ -101,6 +103,11 @@ impl File {
         Ok(self.file()?.set_len(size)?)
     }
 
+    /// Write the data to the disk
+    pub fn sync(&mut self) -> Result<()> {
+        Ok(self.file()?.sync_all()?)
+    }
+
     pub fn remove(&self) -> Result<()> {
         Ok(remove_file(self.path.as_path())?)
     }
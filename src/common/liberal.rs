@@ -11,17 +11,20 @@ pub trait ParseLiteralNumber {
 
 impl ParseLiteralNumber for &str {
     fn literal_number(&self) -> Result<u64, Error> {
-        let (num, unit) = self.split_at(self.len() - 1);
+        // The unit is the last character, and an empty string is not a number
+        let (num, unit) = self.split_at(self.char_indices().last().map_or(0, |(i, _)| i));
         if unit.parse::<u8>().is_err() {
             let mut num = num.parse::<u64>()?;
             for s in &SIZES {
                 if s == &unit.to_uppercase() {
                     return Ok(num);
                 } else {
-                    num *= 1024;
+                    num = num
+                        .checked_mul(1024)
+                        .ok_or_else(|| Error::InvalidArgument(format!("Size is too large: {}", self)))?;
                 }
             }
-            Ok(num)
+            Err(Error::InvalidArgument(format!("Unknown unit of size: {}", self)))
         } else {
             let num = self.parse::<u64>()?;
             Ok(num)
@@ -52,3 +55,19 @@ impl ToDate for u64 {
         return format!("{:.0}d", num);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ParseLiteralNumber;
+
+    #[test]
+    fn test_literal_number() {
+        assert_eq!("100".literal_number().unwrap(), 100);
+        assert_eq!("100k".literal_number().unwrap(), 100 * 1024);
+        assert_eq!("2m".literal_number().unwrap(), 2 * 1024 * 1024);
+        assert!("".literal_number().is_err());
+        assert!("k".literal_number().is_err());
+        assert!("1好".literal_number().is_err());
+        assert!("99999999999t".literal_number().is_err());
+    }
+}
@@ -1,30 +1,35 @@
-use std::{cell::RefCell, rc::Rc};
+use std::sync::{Arc, Mutex};
 
 use crate::features::stack::StackLike;
 
 #[derive(Debug, Clone)]
 pub struct SharedVec<T> {
-    inner: Rc<RefCell<Vec<T>>>,
+    inner: Arc<Mutex<Vec<T>>>,
 }
 
 impl<T> SharedVec<T> {
     pub fn new(list: Vec<T>) -> SharedVec<T> {
         SharedVec {
-            inner: Rc::new(RefCell::new(list)),
+            inner: Arc::new(Mutex::new(list)),
         }
     }
+
+    /// Sort the items, e.g. to keep the order after items are pushed back
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&self, f: F) {
+        self.inner.lock().unwrap().sort_by_key(f)
+    }
 }
 
 impl<T> StackLike<T> for SharedVec<T> {
     fn push(&mut self, item: T) {
-        self.inner.borrow_mut().push(item)
+        self.inner.lock().unwrap().push(item)
     }
 
     fn pop(&mut self) -> Option<T> {
-        self.inner.borrow_mut().pop()
+        self.inner.lock().unwrap().pop()
     }
 
     fn len(&self) -> usize {
-        self.inner.borrow().len()
+        self.inner.lock().unwrap().len()
     }
 }
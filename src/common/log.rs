@@ -0,0 +1,116 @@
+use std::{
+    io::{sink, stderr, stdout, IsTerminal},
+    path::Path,
+};
+
+use time::{macros::format_description, UtcOffset};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::fmt::time::OffsetTime;
+
+use crate::{
+    app::show::common::{use_color, ProgressFormat},
+    common::errors::{Error, Result},
+    features::args::Args,
+};
+
+/// The format of logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// A json object per line
+    Json,
+}
+
+/// How often the log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Rotation {
+        match rotation {
+            LogRotation::Never => Rotation::NEVER,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Weekly => Rotation::WEEKLY,
+        }
+    }
+}
+
+/// The offset of the local time zone
+///
+/// It must be called before any other thread is spawned. Otherwise the offset can't be got soundly,
+/// and UTC is returned.
+pub fn local_offset() -> UtcOffset {
+    UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC)
+}
+
+fn build_appender(path: &Path, rotation: LogRotation, max_files: Option<u64>) -> Result<RollingFileAppender> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidLogFile(path.display().to_string()))?;
+    let directory = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    // The file name is suffixed by the date, e.g. `aget.log.2024-01-01`, if it is rotated
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation.into())
+        .filename_prefix(file_name.to_string_lossy());
+    if let Some(max_files) = max_files {
+        builder = builder.max_log_files(max_files as usize);
+    }
+    builder
+        .build(directory)
+        .map_err(|err| Error::InvalidLogFile(format!("{}: {}", path.display(), err)))
+}
+
+/// Set the global tracing subscriber
+///
+/// Logs are written to the log file if it is given, or to stdout. Json progress events take stdout,
+/// so logs are written to stderr for them, and logs are discarded for the dashboard. Logs are
+/// flushed when the returned guard is dropped.
+pub fn init_log(args: &impl Args, offset: UtcOffset) -> Result<WorkerGuard> {
+    let (writer, guard, ansi) = if let Some(path) = args.log_file() {
+        let appender = build_appender(&path, args.log_rotation(), args.log_max_files())?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        (writer, guard, false)
+    } else if args.tui() {
+        // The dashboard takes the terminal, so logs are discarded without the log file
+        let (writer, guard) = tracing_appender::non_blocking(sink());
+        (writer, guard, false)
+    } else if args.progress() == ProgressFormat::Json {
+        let (writer, guard) = tracing_appender::non_blocking(stderr());
+        (writer, guard, use_color() && stderr().is_terminal())
+    } else {
+        let (writer, guard) = tracing_appender::non_blocking(stdout());
+        (writer, guard, use_color() && stdout().is_terminal())
+    };
+
+    let timer = OffsetTime::new(
+        offset,
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:2]"),
+    );
+
+    let builder = tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_max_level(args.log_level())
+        .with_timer(timer)
+        .with_ansi(ansi);
+    match args.log_format() {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+
+    Ok(guard)
+}
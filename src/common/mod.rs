@@ -7,6 +7,7 @@ pub mod errors;
 pub mod file;
 pub mod liberal;
 pub mod list;
+pub mod log;
 pub mod net;
 pub mod range;
 pub mod size;
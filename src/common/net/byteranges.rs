@@ -0,0 +1,186 @@
+use crate::common::{
+    bytes::bytes_type::{Bytes, BytesMut},
+    errors::{Error, Result},
+    range::RangePair,
+};
+
+/// The maximum size of the part headers or the preamble which are buffered
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// Parse the value of a `Content-Range` header, e.g. `bytes 0-99/1000`
+pub fn parse_content_range(raw: &str) -> Result<RangePair> {
+    let invalid = || Error::InvalidByteRanges(format!("Invalid content-range: {}", raw));
+    let range = raw.trim().strip_prefix("bytes ").ok_or_else(invalid)?;
+    let range = range.split('/').next().ok_or_else(invalid)?;
+    let (begin, end) = range.split_once('-').ok_or_else(invalid)?;
+    let begin = begin.trim().parse::<u64>().map_err(|_| invalid())?;
+    let end = end.trim().parse::<u64>().map_err(|_| invalid())?;
+    if begin > end {
+        return Err(invalid());
+    }
+    Ok(RangePair::new(begin, end))
+}
+
+/// Get the boundary of a `multipart/byteranges` content type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params.next()?.trim().eq_ignore_ascii_case("multipart/byteranges") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case("boundary") {
+            Some(value.trim().trim_matches('"').to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+enum State {
+    Delimiter,
+    Headers,
+    Body { offset: u64, remaining: u64 },
+    End,
+}
+
+/// A streaming parser of `multipart/byteranges` response bodies
+///
+/// The body of each part is located by its `Content-Range` header, so the boundary is only
+/// searched between parts.
+pub struct ByteRangesParser {
+    delimiter: Vec<u8>,
+    buf: BytesMut,
+    state: State,
+}
+
+impl ByteRangesParser {
+    pub fn new(boundary: &str) -> ByteRangesParser {
+        ByteRangesParser {
+            delimiter: format!("--{}", boundary).into_bytes(),
+            buf: BytesMut::new(),
+            state: State::Delimiter,
+        }
+    }
+
+    /// Whether the close delimiter is met
+    pub fn is_end(&self) -> bool {
+        matches!(self.state, State::End)
+    }
+
+    /// Feed a chunk of the response body, and return the offsets and the bytes of parts' bodies
+    /// in the chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<(u64, Bytes)>> {
+        let mut parts = vec![];
+        if self.is_end() {
+            return Ok(parts);
+        }
+        self.buf.extend_from_slice(chunk);
+
+        loop {
+            match self.state {
+                State::Delimiter => {
+                    let index = match find(&self.buf, &self.delimiter) {
+                        Some(index) => index,
+                        None => break,
+                    };
+                    let rest = &self.buf[index + self.delimiter.len()..];
+                    if rest.starts_with(b"--") {
+                        self.state = State::End;
+                        self.buf.clear();
+                        break;
+                    }
+                    // The delimiter line can be ended with transport padding
+                    match find(rest, b"\r\n") {
+                        Some(end) => {
+                            let _ = self.buf.split_to(index + self.delimiter.len() + end + 2);
+                            self.state = State::Headers;
+                        }
+                        None => break,
+                    }
+                }
+                State::Headers => {
+                    let end = match find(&self.buf, b"\r\n\r\n") {
+                        Some(end) => end,
+                        None => break,
+                    };
+                    let headers = self.buf.split_to(end + 4);
+                    let headers = String::from_utf8_lossy(&headers);
+                    let content_range = headers
+                        .lines()
+                        .filter_map(|line| line.split_once(':'))
+                        .find(|(key, _)| key.trim().eq_ignore_ascii_case("content-range"))
+                        .map(|(_, value)| value)
+                        .ok_or_else(|| Error::InvalidByteRanges(format!("No content-range in part: {}", headers)))?;
+                    let pair = parse_content_range(content_range)?;
+                    self.state = State::Body {
+                        offset: pair.begin,
+                        remaining: pair.length(),
+                    };
+                }
+                State::Body { offset, remaining } => {
+                    if self.buf.is_empty() {
+                        break;
+                    }
+                    let len = std::cmp::min(remaining, self.buf.len() as u64);
+                    parts.push((offset, self.buf.split_to(len as usize).freeze()));
+                    self.state = if remaining == len {
+                        State::Delimiter
+                    } else {
+                        State::Body {
+                            offset: offset + len,
+                            remaining: remaining - len,
+                        }
+                    };
+                }
+                State::End => break,
+            }
+        }
+
+        if !matches!(self.state, State::Body { .. }) && self.buf.len() > MAX_HEADER_SIZE {
+            return Err(Error::InvalidByteRanges("Too long part headers".to_owned()));
+        }
+        Ok(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{multipart_boundary, parse_content_range, ByteRangesParser};
+
+    #[test]
+    fn test_byteranges_parser() {
+        assert_eq!(
+            multipart_boundary(r#"multipart/byteranges; boundary="THIS_STRING""#).as_deref(),
+            Some("THIS_STRING")
+        );
+        assert_eq!(multipart_boundary("text/plain"), None);
+        assert_eq!(parse_content_range("bytes 21010-47021/47022").unwrap().length(), 26012);
+        assert!(parse_content_range("bytes */47022").is_err());
+
+        let body = b"preamble\r\n--AB\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-4/100\r\n\r\nhello\r\n\
+                     --AB  \r\ncontent-range: bytes 50-55/100\r\n\r\n--AB--\r\n--AB--\r\nepilogue";
+        // Feed byte by byte, and in one piece
+        for size in [1, body.len()] {
+            let mut parser = ByteRangesParser::new("AB");
+            let mut data: Vec<(u64, Vec<u8>)> = vec![];
+            for chunk in body.chunks(size) {
+                for (offset, bytes) in parser.feed(chunk).unwrap() {
+                    match data.last_mut() {
+                        Some((o, d)) if *o + d.len() as u64 == offset => d.extend_from_slice(&bytes),
+                        _ => data.push((offset, bytes.to_vec())),
+                    }
+                }
+            }
+            assert!(parser.is_end());
+            assert_eq!(data, vec![(0, b"hello".to_vec()), (50, b"--AB--".to_vec())]);
+        }
+
+        let mut parser = ByteRangesParser::new("AB");
+        assert!(parser.feed(b"--AB\r\nContent-Type: text/plain\r\n\r\n").is_err());
+    }
+}
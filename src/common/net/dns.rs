@@ -0,0 +1,105 @@
+use std::net::{IpAddr, SocketAddr};
+
+use hickory_resolver::{
+    config::{ConnectionConfig, LookupIpStrategy, NameServerConfig, ResolverConfig},
+    net::runtime::TokioRuntimeProvider,
+    TokioResolver,
+};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+
+use crate::common::errors::{Error, Result};
+
+/// The address family which is used to connect to a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::Any => true,
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// Dns resolver for `HttpClient`
+///
+/// It resolves names by the given name servers, or by the system resolver if there is no name
+/// server, and only keeps the addresses of the given `IpFamily`.
+#[derive(Clone)]
+pub struct AgetResolver {
+    family: IpFamily,
+    resolver: Option<TokioResolver>,
+}
+
+impl AgetResolver {
+    pub fn new(family: IpFamily, name_servers: &[SocketAddr]) -> Result<AgetResolver> {
+        let resolver = if name_servers.is_empty() {
+            None
+        } else {
+            let mut config = ResolverConfig::from_parts(None, vec![], vec![]);
+            for addr in name_servers {
+                let connections = [ConnectionConfig::udp(), ConnectionConfig::tcp()]
+                    .into_iter()
+                    .map(|mut conn| {
+                        conn.port = addr.port();
+                        conn
+                    })
+                    .collect();
+                config.add_name_server(NameServerConfig::new(addr.ip(), true, connections));
+            }
+
+            let mut builder = TokioResolver::builder_with_config(config, TokioRuntimeProvider::default());
+            builder.options_mut().ip_strategy = match family {
+                IpFamily::Any => LookupIpStrategy::Ipv4AndIpv6,
+                IpFamily::V4 => LookupIpStrategy::Ipv4Only,
+                IpFamily::V6 => LookupIpStrategy::Ipv6Only,
+            };
+            Some(builder.build().map_err(|err| Error::DnsError(err.to_string()))?)
+        };
+
+        Ok(AgetResolver { family, resolver })
+    }
+
+    /// Resolve the host to all its addresses
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let ips: Vec<IpAddr> = if let Some(resolver) = &self.resolver {
+            resolver
+                .lookup_ip(host)
+                .await
+                .map_err(|err| Error::DnsError(err.to_string()))?
+                .iter()
+                .collect()
+        } else {
+            tokio::net::lookup_host((host, 0))
+                .await?
+                .map(|addr| addr.ip())
+                .collect()
+        };
+
+        let ips: Vec<IpAddr> = ips.into_iter().filter(|ip| self.family.contains(ip)).collect();
+        if ips.is_empty() {
+            return Err(Error::DnsError(format!("No {:?} address for {}", self.family, host)));
+        }
+        Ok(ips)
+    }
+}
+
+impl Resolve for AgetResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let ips = resolver.lookup(name.as_str()).await?;
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, Weak},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::common::net::Url;
+
+/// host -> (semaphore, limit)
+///
+/// The semaphores are held by the `HostLimit`s, so they are dropped with the tasks.
+type HostSemaphores = Mutex<HashMap<String, (Weak<Semaphore>, u64)>>;
+
+static HOST_SEMAPHORES: OnceLock<HostSemaphores> = OnceLock::new();
+
+/// A limit of the number of connections to a host, which is shared by all tasks in the process
+///
+/// The host has one limit, which is of the first task while the limit is held. The limits of the
+/// other tasks to the host are ignored.
+#[derive(Clone)]
+pub struct HostLimit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl HostLimit {
+    pub fn new(url: &Url, limit: u64) -> HostLimit {
+        let host = url.host_str().unwrap_or("").to_lowercase();
+        let mut semaphores = HOST_SEMAPHORES
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap();
+        semaphores.retain(|_, (semaphore, _)| semaphore.strong_count() > 0);
+        if let Some((semaphore, held)) = semaphores.get(&host) {
+            if let Some(semaphore) = semaphore.upgrade() {
+                if *held != limit {
+                    tracing::warn!(
+                        "The connections to {} are limited to {} by another task, instead of {}",
+                        host,
+                        held,
+                        limit
+                    );
+                }
+                return HostLimit { semaphore };
+            }
+        }
+        let semaphore = Arc::new(Semaphore::new(limit as usize));
+        semaphores.insert(host, (Arc::downgrade(&semaphore), limit));
+        HostLimit { semaphore }
+    }
+
+    /// Wait until a connection to the host is available. The connection is released when the
+    /// permit is dropped.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore.clone().acquire_owned().await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::HostLimit;
+
+    #[test]
+    fn test_host_limit() {
+        let url = "http://host-limit.test/a".parse().unwrap();
+        let limit = HostLimit::new(&url, 2);
+
+        // Tasks to the same host share the connections, even if their ports or limits are different
+        let other = HostLimit::new(&"https://host-limit.test:8443/b".parse().unwrap(), 2);
+        assert!(Arc::ptr_eq(&limit.semaphore, &other.semaphore));
+        let _permit = limit.semaphore.clone().try_acquire_owned().unwrap();
+        assert_eq!(other.semaphore.available_permits(), 1);
+        let larger = HostLimit::new(&url, 4);
+        assert!(Arc::ptr_eq(&limit.semaphore, &larger.semaphore));
+
+        // Other hosts have their own connections
+        let host = HostLimit::new(&"http://other.host-limit.test/a".parse().unwrap(), 2);
+        assert_eq!(host.semaphore.available_permits(), 2);
+
+        // The connections are dropped with the tasks, and then the next limit is used
+        drop((limit, other, larger, _permit));
+        assert_eq!(HostLimit::new(&url, 4).semaphore.available_permits(), 4);
+    }
+}
@@ -1,12 +1,32 @@
+pub mod byteranges;
+pub mod dns;
+pub mod host_limit;
 pub mod net;
+pub mod pac;
+pub mod pool;
 
 pub use http::Uri;
 pub use reqwest::{
-    header::{HeaderMap, HeaderName},
-    Client as HttpClient, Method, Proxy, Request, Response,
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client as HttpClient, Method, NoProxy, Proxy, Request, Response,
 };
 pub use url::Url;
 
+use serde::{Deserialize, Serialize};
+
+/// The http version used to connect to servers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpVersion {
+    /// Only HTTP/1.1, each concurrent request has its own connection
+    #[default]
+    Http1,
+    /// HTTP/2 if the server supports it by TLS ALPN, else HTTP/1.1
+    Http2,
+    /// HTTP/2 without negotiation, which also works for cleartext `http://` urls
+    Http2PriorKnowledge,
+}
+
 #[derive(Debug)]
 pub enum ContentLengthValue {
     RangeLength(u64),
@@ -1,9 +1,24 @@
-use std::time::Duration;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::redirect::Policy;
 
-use crate::common::{
-    errors::{Error, Result},
-    net::{ContentLengthValue, HeaderMap, HeaderName, HttpClient, Method, Proxy, Response, Url},
-    range::RangePair,
+use crate::{
+    arguments::options::DownloadOptions,
+    common::{
+        errors::{Error, Result},
+        net::{
+            dns::{AgetResolver, IpFamily},
+            pac::Pac,
+            ContentLengthValue, HeaderMap, HeaderName, HeaderValue, HttpClient, HttpVersion, Method, NoProxy, Proxy,
+            Response, Url,
+        },
+        range::RangePair,
+    },
 };
 
 pub fn parse_header(raw: &str) -> Result<(&str, &str), Error> {
@@ -16,6 +31,14 @@ pub fn parse_header(raw: &str) -> Result<(&str, &str), Error> {
     Err(Error::InvalidHeader(raw.to_string()))
 }
 
+/// Check that the name and the value can be sent as a header
+pub fn header_pair(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), Error> {
+    let invalid = || Error::InvalidHeader(format!("{}: {}", name, value));
+    let name = name.parse::<HeaderName>().map_err(|_| invalid())?;
+    let value = value.parse::<HeaderValue>().map_err(|_| invalid())?;
+    Ok((name, value))
+}
+
 pub fn parse_headers<'a, I: IntoIterator<Item = &'a str>>(raws: I) -> Result<Vec<(&'a str, &'a str)>, Error> {
     let mut headers = vec![];
     for raw in raws {
@@ -25,39 +48,328 @@ pub fn parse_headers<'a, I: IntoIterator<Item = &'a str>>(raws: I) -> Result<Vec
     Ok(headers)
 }
 
+/// Parse an ip address which can be wrapped with brackets, e.g. `[::1]`
+fn parse_ip(raw: &str) -> Result<IpAddr, Error> {
+    let ip = raw.trim();
+    let ip = ip.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(ip);
+    ip.parse::<IpAddr>().map_err(|_| Error::InvalidAddress(raw.to_string()))
+}
+
+/// Parse a resolving entry as curl's `--resolve`
+///
+/// The format is `host:port:addr[,addr]...`, e.g. `example.com:443:127.0.0.1,[::1]`
+pub fn parse_resolve(raw: &str) -> Result<(String, Vec<SocketAddr>), Error> {
+    let mut parts = raw.splitn(3, ':');
+    let (host, port, addrs) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(host), Some(port), Some(addrs)) if !host.is_empty() => (host, port, addrs),
+        _ => return Err(Error::InvalidAddress(raw.to_string())),
+    };
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| Error::InvalidAddress(raw.to_string()))?;
+
+    let mut socket_addrs = vec![];
+    for addr in addrs.split(',') {
+        socket_addrs.push(SocketAddr::new(parse_ip(addr)?, port));
+    }
+    Ok((host.to_string(), socket_addrs))
+}
+
+/// Parse a dns server address, `addr[:port]`, the default port is 53
+pub fn parse_dns_server(raw: &str) -> Result<SocketAddr, Error> {
+    if let Ok(addr) = raw.trim().parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    Ok(SocketAddr::new(parse_ip(raw)?, 53))
+}
+
+/// Options for building a `HttpClient`
+#[derive(Debug, Clone, Default)]
+pub struct HttpClientOptions {
+    pub headers: Vec<(String, String)>,
+    pub timeout: Duration,
+    pub dns_timeout: Duration,
+    pub keep_alive: Duration,
+    pub skip_verify_tls_cert: bool,
+    pub http_version: HttpVersion,
+    pub compressed: bool,
+    pub no_redirect: bool,
+    pub max_redirects: u64,
+    pub location_trusted: bool,
+    pub proxy: Option<String>,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub proxy_user: Option<String>,
+    pub proxy_pac: Option<PathBuf>,
+    pub resolve: Vec<(String, Vec<SocketAddr>)>,
+    pub ip_family: IpFamily,
+    pub dns_servers: Vec<SocketAddr>,
+    pub unix_socket: Option<PathBuf>,
+}
+
+impl HttpClientOptions {
+    pub fn from_options(options: &DownloadOptions) -> HttpClientOptions {
+        // As curl, an entry of `--resolve` is only for its port. The client ignores the ports of the
+        // addresses, so the entries for other ports of the url are dropped
+        let port = options.url().port_or_known_default().unwrap_or(0);
+        let resolve = options
+            .resolve()
+            .into_iter()
+            .filter(|(_, addrs)| addrs.iter().any(|addr| addr.port() == port))
+            .collect();
+        HttpClientOptions {
+            headers: options
+                .headers()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            timeout: options.timeout(),
+            dns_timeout: options.dns_timeout(),
+            keep_alive: options.keep_alive(),
+            skip_verify_tls_cert: options.skip_verify_tls_cert(),
+            http_version: options.http_version(),
+            compressed: options.compressed(),
+            no_redirect: options.disable_redirects(),
+            max_redirects: options.max_redirects(),
+            location_trusted: options.location_trusted(),
+            proxy: options.proxy().map(|v| v.to_string()),
+            http_proxy: options.http_proxy(),
+            https_proxy: options.https_proxy(),
+            no_proxy: options.no_proxy(),
+            proxy_user: options.proxy_user(),
+            proxy_pac: options.proxy_pac(),
+            resolve,
+            ip_family: options.ip_family(),
+            dns_servers: options.dns_servers(),
+            unix_socket: options.unix_socket(),
+        }
+    }
+
+    /// Remove the headers of credentials, which must not be sent to other origins
+    pub fn without_credentials(&self) -> HttpClientOptions {
+        let mut options = self.clone();
+        options
+            .headers
+            .retain(|(k, _)| !["authorization", "cookie", "cookie2"].contains(&k.to_lowercase().as_str()));
+        options
+    }
+}
+
 /// Builder a http client of curl
-pub fn build_http_client(
-    headers: &[(&str, &str)],
-    timeout: Duration,
-    dns_timeout: Duration,
-    keep_alive: Duration,
-    skip_verify_tls_cert: bool,
-    proxy: Option<&str>,
-) -> Result<HttpClient> {
+pub fn build_http_client(options: &HttpClientOptions) -> Result<HttpClient> {
     let mut default_headers = HeaderMap::new();
-    headers.iter().for_each(|(k, v)| {
-        default_headers.insert(k.parse::<HeaderName>().unwrap(), v.parse().unwrap());
-    });
+    for (k, v) in options.headers.iter() {
+        let (name, value) = header_pair(k, v)?;
+        default_headers.insert(name, value);
+    }
     if !default_headers.contains_key("accept") {
         default_headers.insert("accept", "*/*".parse().unwrap());
     }
+    // The offsets of ranges are only meaningful for the identity encoding
+    if !options.compressed && !default_headers.contains_key("accept-encoding") {
+        default_headers.insert("accept-encoding", "identity".parse().unwrap());
+    }
 
+    // The timeout is for waiting the next data, so a long response, e.g. under a speed limit, is
+    // not aborted while it is still receiving
     let mut client = HttpClient::builder()
-        .timeout(timeout)
-        .connect_timeout(dns_timeout)
-        .tcp_keepalive(keep_alive)
+        .read_timeout(options.timeout)
+        .connect_timeout(options.dns_timeout)
+        .tcp_keepalive(options.keep_alive)
         .default_headers(default_headers);
 
-    if skip_verify_tls_cert {
+    if options.skip_verify_tls_cert {
         client = client.danger_accept_invalid_certs(true);
     }
 
-    if let Some(url) = proxy {
-        client = client.proxy(Proxy::all(url)?);
+    if !options.compressed {
+        client = client.no_gzip().no_brotli().no_deflate();
+    }
+
+    let (no_redirect, max_redirects) = (options.no_redirect, options.max_redirects);
+    client = client.redirect(Policy::custom(move |attempt| {
+        if no_redirect {
+            attempt.stop()
+        } else if attempt.previous().len() as u64 > max_redirects {
+            attempt.error(Error::TooManyRedirects(max_redirects))
+        } else {
+            if let Some(previous) = attempt.previous().last() {
+                tracing::debug!("Redirect {}: {} -> {}", attempt.status(), previous, attempt.url());
+            }
+            attempt.follow()
+        }
+    }));
+
+    // Concurrent requests share one connection of HTTP/2 as streams, so enlarge the flow control
+    // window as needed
+    match options.http_version {
+        HttpVersion::Http1 => client = client.http1_only(),
+        HttpVersion::Http2 => client = client.http2_adaptive_window(true),
+        HttpVersion::Http2PriorKnowledge => client = client.http2_prior_knowledge().http2_adaptive_window(true),
+    }
+
+    for proxy in build_proxies(options)? {
+        client = client.proxy(proxy);
+    }
+
+    // Resolve names with the custom resolver only when it is needed
+    if options.ip_family != IpFamily::Any || !options.dns_servers.is_empty() {
+        let resolver = AgetResolver::new(options.ip_family, &options.dns_servers)?;
+        client = client.dns_resolver(Arc::new(resolver));
+    }
+
+    // Bind the unspecified address of the family, so that ip literal hosts of the other family fail
+    match options.ip_family {
+        IpFamily::V4 => client = client.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        IpFamily::V6 => client = client.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        IpFamily::Any => {}
+    }
+
+    // Overrides are applied on top of the resolver. The ports of the addresses are ignored by the
+    // client, so the overrides must be for the port of the url
+    for (host, addrs) in options.resolve.iter() {
+        client = client.resolve_to_addrs(host, addrs);
     }
+
+    // All connections go through the unix socket, which ignores above tcp and proxy options
+    if let Some(path) = &options.unix_socket {
+        #[cfg(unix)]
+        {
+            client = client.unix_socket(path.clone());
+        }
+        #[cfg(not(unix))]
+        {
+            return Err(Error::NetError(format!("Unix socket is not supported: {:?}", path)));
+        }
+    }
+
     Ok(client.build()?)
 }
 
+/// Build the proxies of the options, in the order of precedence: the PAC file, the
+/// protocol-specific proxies and then the proxy for all protocols
+///
+/// The exclusion list and the credentials are applied to all of them.
+fn build_proxies(options: &HttpClientOptions) -> Result<Vec<Proxy>> {
+    let mut proxies = vec![];
+    if let Some(path) = &options.proxy_pac {
+        let pac = Pac::from_file(path)?;
+        proxies.push(Proxy::custom(move |url| match pac.find_proxy(url) {
+            Ok(proxy) => proxy,
+            Err(err) => {
+                tracing::warn!("PAC fails for {}, connect directly: {}", url, err);
+                None
+            }
+        }));
+    }
+    if let Some(url) = &options.https_proxy {
+        proxies.push(Proxy::https(url)?);
+    }
+    if let Some(url) = &options.http_proxy {
+        proxies.push(Proxy::http(url)?);
+    }
+    if let Some(url) = &options.proxy {
+        proxies.push(Proxy::all(url)?);
+    }
+
+    let no_proxy = options.no_proxy.as_deref().and_then(NoProxy::from_string);
+    let user = match &options.proxy_user {
+        Some(raw) => Some(
+            raw.split_once(':')
+                .ok_or_else(|| Error::InvalidProxyUser(raw.to_string()))?,
+        ),
+        None => None,
+    };
+    Ok(proxies
+        .into_iter()
+        .map(|proxy| {
+            let proxy = proxy.no_proxy(no_proxy.clone());
+            match user {
+                Some((username, password)) => proxy.basic_auth(username, password),
+                None => proxy,
+            }
+        })
+        .collect())
+}
+
+/// Build a `HttpClient` for each of the proxies
+///
+/// Each returned client is named by its proxy.
+pub fn build_proxy_clients(options: &HttpClientOptions, proxies: &[String]) -> Result<Vec<(String, HttpClient)>> {
+    let mut clients = vec![];
+    for proxy in proxies {
+        let mut opts = options.clone();
+        // The proxy is the only one of the client. The protocol-specific proxies, e.g. from the
+        // environment, would take all requests first
+        opts.proxy = Some(proxy.clone());
+        opts.http_proxy = None;
+        opts.https_proxy = None;
+        opts.proxy_pac = None;
+        clients.push((proxy.clone(), build_http_client(&opts)?));
+    }
+    Ok(clients)
+}
+
+/// Build `connections` `HttpClient`s, each of which has its own HTTP/2 connection
+///
+/// The given client is the first one, whose connection is reused. Each returned client is named
+/// by its index.
+pub fn build_http2_clients(
+    options: &HttpClientOptions,
+    client: &HttpClient,
+    connections: u64,
+) -> Result<Vec<(String, HttpClient)>> {
+    let mut clients = vec![("connection #0".to_owned(), client.clone())];
+    for i in 1..connections {
+        clients.push((format!("connection #{}", i), build_http_client(options)?));
+    }
+    Ok(clients)
+}
+
+/// Resolve all addresses of the url's host once, and build a `HttpClient` for each of them
+///
+/// Each returned client is named by its address.
+pub async fn build_spread_clients(options: &HttpClientOptions, url: &Url) -> Result<Vec<(String, HttpClient)>> {
+    let host = match url.host() {
+        Some(url::Host::Domain(host)) if options.unix_socket.is_none() => host.to_owned(),
+        // Ip literal or unix socket has only one address
+        _ => {
+            return Ok(vec![(
+                url.host_str().unwrap_or("").to_owned(),
+                build_http_client(options)?,
+            )])
+        }
+    };
+
+    // As curl, an entry of `--resolve` is only for its host and port
+    let port = url.port_or_known_default().unwrap_or(0);
+    let addrs = if let Some((_, addrs)) = options
+        .resolve
+        .iter()
+        .rev()
+        .find(|(h, addrs)| h.eq_ignore_ascii_case(&host) && addrs.iter().any(|addr| addr.port() == port))
+    {
+        addrs.clone()
+    } else {
+        AgetResolver::new(options.ip_family, &options.dns_servers)?
+            .lookup(&host)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, 0))
+            .collect()
+    };
+    tracing::debug!("Spread {} to addresses: {:?}", host, addrs);
+
+    let mut clients = vec![];
+    for addr in addrs {
+        let mut opts = options.clone();
+        opts.resolve.push((host.clone(), vec![addr]));
+        clients.push((addr.ip().to_string(), build_http_client(&opts)?));
+    }
+    Ok(clients)
+}
+
 /// Check whether the response is success
 /// Check if status is within 200-299.
 pub fn is_success(resp: &reqwest::Response) -> Result<(), Error> {
@@ -69,6 +381,16 @@ pub fn is_success(resp: &reqwest::Response) -> Result<(), Error> {
     }
 }
 
+/// Check that the range response is not encoded, e.g. by gzip
+pub fn is_identity(resp: &reqwest::Response) -> Result<(), Error> {
+    match resp.headers().get("content-encoding").map(|v| v.to_str().unwrap_or("")) {
+        Some(encoding) if !encoding.trim().eq_ignore_ascii_case("identity") => {
+            Err(Error::EncodedRangeResponse(encoding.to_owned()))
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Send a request with a range header, returning the final url
 pub async fn redirect(client: &HttpClient, method: Method, url: Url, data: Option<String>) -> Result<Url> {
     let mut req = client.request(method.clone(), url.clone()).header("range", "bytes=0-1");
@@ -83,29 +405,75 @@ pub async fn redirect(client: &HttpClient, method: Method, url: Url, data: Optio
     Ok(resp.url().clone())
 }
 
-/// Get the content length of the resource
+/// Follow redirects and get the content length of the resource
+///
+/// Redirects are followed here rather than by the client, so that the headers of credentials are
+/// removed after the redirection to another origin, unless `location_trusted` is set.
 pub async fn redirect_and_contentlength(
-    client: &HttpClient,
+    options: &HttpClientOptions,
     method: Method,
     url: Url,
     data: Option<String>,
 ) -> Result<(Url, ContentLengthValue)> {
-    let mut req = client.request(method.clone(), url.clone()).header("range", "bytes=0-1");
-    if let Some(d) = data.clone() {
-        req = req.body(d);
-    }
+    let mut probe_options = options.clone();
+    probe_options.no_redirect = true;
+    let mut client = build_http_client(&probe_options)?;
 
-    let resp = req.send().await?;
+    let origin = url.origin();
+    let (mut method, mut url, mut data) = (method, url, data);
+    let mut redirects = 0;
+    let mut trusted = true;
+    let resp = loop {
+        let mut req = client.request(method.clone(), url.clone()).header("range", "bytes=0-1");
+        if let Some(d) = data.clone() {
+            req = req.body(d);
+        }
+
+        let resp = req.send().await?;
+        let status = resp.status().as_u16();
+        if ![301, 302, 303, 307, 308].contains(&status) {
+            break resp;
+        }
+
+        let location = resp
+            .headers()
+            .get("location")
+            .ok_or_else(|| Error::NoLocation(url.to_string()))?
+            .to_str()?;
+        let next = join_url(&url, location)?;
+        if options.no_redirect {
+            return Err(Error::Redirect(next.to_string()));
+        }
+        redirects += 1;
+        if redirects > options.max_redirects {
+            return Err(Error::TooManyRedirects(options.max_redirects));
+        }
+        tracing::debug!("Redirect {}: {} {} -> {}", redirects, status, url, next);
+
+        // Browsers change the method to GET, except for 307 and 308
+        if status == 303 || (method == Method::POST && (status == 301 || status == 302)) {
+            method = Method::GET;
+            data = None;
+        }
+        if trusted && !options.location_trusted && next.origin() != origin {
+            tracing::debug!("Remove credentials for the other origin: {}", next);
+            client = build_http_client(&probe_options.without_credentials())?;
+            trusted = false;
+        }
+        url = next;
+    };
     is_success(&resp)?;
 
     let url = resp.url().clone();
 
     let status_code = resp.status();
     if status_code.as_u16() == 206 {
-        let cl_str = resp.headers().get("content-range").unwrap().to_str().unwrap();
-        let index = cl_str.find('/').unwrap();
-        let length = cl_str[index + 1..].parse::<u64>()?;
-        return Ok((url, ContentLengthValue::RangeLength(length)));
+        is_identity(&resp)?;
+        let length = match complete_length(resp.headers().get("content-range"))? {
+            Some(length) => ContentLengthValue::RangeLength(length),
+            None => ContentLengthValue::NoLength,
+        };
+        return Ok((url, length));
     } else {
         let content_length = resp.content_length();
         if let Some(length) = content_length {
@@ -116,6 +484,26 @@ pub async fn redirect_and_contentlength(
     }
 }
 
+/// Get the complete length from the `Content-Range` header of a range response, e.g. `bytes 0-1/1000`
+///
+/// The length is unknown for `bytes 0-1/*`. A missing or invalid header is a fault of the server.
+fn complete_length(content_range: Option<&HeaderValue>) -> Result<Option<u64>> {
+    let raw = match content_range {
+        Some(value) => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        None => {
+            return Err(Error::InvaildHeader(
+                "No content-range in the range response".to_owned(),
+            ))
+        }
+    };
+    let invalid = || Error::InvaildHeader(format!("content-range: {}", raw));
+    let (_, length) = raw.split_once('/').ok_or_else(invalid)?;
+    match length.trim() {
+        "*" => Ok(None),
+        length => length.parse::<u64>().map(Some).map_err(|_| invalid()),
+    }
+}
+
 /// Send a request
 pub async fn request(
     client: &HttpClient,
@@ -139,12 +527,111 @@ pub async fn request(
     return Ok(resp);
 }
 
+/// Send a request with a multi-range header, e.g. `Range: bytes=0-9,20-29`
+pub async fn request_ranges(
+    client: &HttpClient,
+    method: Method,
+    url: Url,
+    data: Option<String>,
+    ranges: &[RangePair],
+) -> Result<Response> {
+    let ranges: Vec<String> = ranges.iter().map(|p| format!("{}-{}", p.begin, p.end)).collect();
+    let mut req = client
+        .request(method, url)
+        .header("range", format!("bytes={}", ranges.join(",")));
+    if let Some(d) = data {
+        req = req.body(d);
+    }
+
+    let resp = req.send().await?;
+    is_success(&resp)?;
+    Ok(resp)
+}
+
+/// Join the url of a response, e.g. a `Location` header or an uri of a m3u8 file, to the url of
+/// the request
+///
+/// An invalid url is a fault of the server rather than of the arguments.
 pub fn join_url(base_url: &Url, url: &str) -> Result<Url> {
+    let invalid = |_| Error::InvalidResponseUrl(url.to_owned());
     let new_url: Url = if !url.to_lowercase().starts_with("http") {
-        let base_url = Url::parse(&format!("{}", base_url))?;
-        base_url.join(url)?.as_str().parse()?
+        base_url.join(url).map_err(invalid)?
     } else {
-        url.parse()?
+        url.parse().map_err(invalid)?
     };
     Ok(new_url)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        complete_length, header_pair, join_url, parse_dns_server, parse_header, parse_resolve, HttpClientOptions,
+    };
+    use crate::{
+        arguments::options::DownloadOptions,
+        common::{
+            errors::{exit_code, Error},
+            net::HeaderValue,
+        },
+    };
+
+    #[test]
+    fn test_header_pair() {
+        let (name, value) = parse_header("X-Token: a b").unwrap();
+        assert!(header_pair(name, value).is_ok());
+        let (name, value) = parse_header("bad name: x").unwrap();
+        assert!(matches!(header_pair(name, value), Err(Error::InvalidHeader(_))));
+        assert!(matches!(header_pair("x-token", "a\nb"), Err(Error::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn test_parse_resolve() {
+        let (host, addrs) = parse_resolve("example.com:443:127.0.0.1,[::1]").unwrap();
+        assert_eq!(host, "example.com");
+        assert_eq!(
+            addrs,
+            vec!["127.0.0.1:443".parse().unwrap(), "[::1]:443".parse().unwrap()]
+        );
+
+        assert!(parse_resolve("example.com:443").is_err());
+        assert!(parse_resolve("example.com:port:127.0.0.1").is_err());
+
+        assert_eq!(parse_dns_server("1.1.1.1").unwrap(), "1.1.1.1:53".parse().unwrap());
+        assert_eq!(parse_dns_server("[::1]:5353").unwrap(), "[::1]:5353".parse().unwrap());
+    }
+
+    #[test]
+    fn test_resolve_port() {
+        // Only the entries for the port of the url are used, as curl
+        let (host, addrs) = parse_resolve("example.com:443:127.0.0.1").unwrap();
+        let (other, other_addrs) = parse_resolve("example.com:8443:127.0.0.2").unwrap();
+        let options = DownloadOptions::new("https://example.com/a".parse().unwrap())
+            .with_resolve(host, addrs.clone())
+            .with_resolve(other, other_addrs);
+        let resolve = HttpClientOptions::from_options(&options).resolve;
+        assert_eq!(resolve, vec![("example.com".to_owned(), addrs)]);
+    }
+
+    #[test]
+    fn test_faults_of_responses() {
+        let length = |raw: &'static str| complete_length(Some(&HeaderValue::from_static(raw)));
+        assert_eq!(length("bytes 0-1/1000").unwrap(), Some(1000));
+        assert_eq!(length("bytes 0-1/*").unwrap(), None);
+
+        // Invalid responses are network errors rather than usage errors
+        for err in [
+            length("bytes 0-1/x").unwrap_err(),
+            length("bytes 0-1").unwrap_err(),
+            complete_length(None).unwrap_err(),
+            join_url(&"http://example.com/a".parse().unwrap(), "http://[::1").unwrap_err(),
+        ] {
+            assert_eq!(err.exit_code(), exit_code::NETWORK, "{}", err);
+        }
+        assert_eq!(
+            join_url(&"http://example.com/a/b".parse().unwrap(), "../c")
+                .unwrap()
+                .as_str(),
+            "http://example.com/c"
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{ToSocketAddrs, UdpSocket},
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use boa_engine::{js_string, Context, JsResult, JsValue, NativeFunction, Source};
+
+use crate::common::{
+    errors::{Error, Result},
+    net::Url,
+};
+
+/// The predefined functions of PAC files, except `dnsResolve` and `myIpAddress` which are native
+const PAC_UTILS: &str = r#"
+function isPlainHostName(host) {
+    return host.indexOf('.') < 0;
+}
+function dnsDomainIs(host, domain) {
+    return host.length >= domain.length && host.substring(host.length - domain.length) == domain;
+}
+function localHostOrDomainIs(host, hostdom) {
+    return host == hostdom || hostdom.lastIndexOf(host + '.', 0) == 0;
+}
+function isResolvable(host) {
+    return dnsResolve(host) != null;
+}
+function convertAddr(ip) {
+    var b = ip.split('.');
+    return ((b[0] & 0xff) << 24) | ((b[1] & 0xff) << 16) | ((b[2] & 0xff) << 8) | (b[3] & 0xff);
+}
+function isInNet(host, pattern, mask) {
+    var ip = /^\d+\.\d+\.\d+\.\d+$/.test(host) ? host : dnsResolve(host);
+    if (ip == null) {
+        return false;
+    }
+    return (convertAddr(ip) & convertAddr(mask)) == (convertAddr(pattern) & convertAddr(mask));
+}
+function dnsDomainLevels(host) {
+    return host.split('.').length - 1;
+}
+function shExpMatch(str, shexp) {
+    var re = shexp.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.');
+    return new RegExp('^' + re + '$').test(str);
+}
+function pacNow(args) {
+    var gmt = args.length > 0 && args[args.length - 1] == 'GMT';
+    var rest = gmt ? Array.prototype.slice.call(args, 0, args.length - 1) : Array.prototype.slice.call(args);
+    var d = new Date();
+    return {
+        gmt: gmt,
+        args: rest,
+        day: gmt ? d.getUTCDay() : d.getDay(),
+        date: gmt ? d.getUTCDate() : d.getDate(),
+        month: gmt ? d.getUTCMonth() : d.getMonth(),
+        year: gmt ? d.getUTCFullYear() : d.getFullYear(),
+        seconds: gmt
+            ? d.getUTCHours() * 3600 + d.getUTCMinutes() * 60 + d.getUTCSeconds()
+            : d.getHours() * 3600 + d.getMinutes() * 60 + d.getSeconds()
+    };
+}
+function pacInRange(value, begin, end) {
+    return begin <= end ? begin <= value && value <= end : value >= begin || value <= end;
+}
+function weekdayRange() {
+    var now = pacNow(arguments);
+    var days = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+    var begin = days.indexOf(now.args[0]);
+    var end = now.args.length > 1 ? days.indexOf(now.args[1]) : begin;
+    return pacInRange(now.day, begin, end);
+}
+function dateRange() {
+    var now = pacNow(arguments);
+    var months = ['JAN', 'FEB', 'MAR', 'APR', 'MAY', 'JUN', 'JUL', 'AUG', 'SEP', 'OCT', 'NOV', 'DEC'];
+    // Convert each argument to a [kind, value] pair, kind is one of 'date', 'month' and 'year'
+    var parts = now.args.map(function (a) {
+        if (typeof a == 'string' && months.indexOf(a) >= 0) {
+            return ['month', months.indexOf(a)];
+        }
+        a = Number(a);
+        return a > 31 ? ['year', a] : ['date', a];
+    });
+    var half = parts.length == 1 ? 1 : parts.length / 2;
+    var begin = parts.slice(0, half);
+    var end = parts.length == 1 ? begin : parts.slice(half);
+    var key = function (ps, fallback) {
+        var r = { year: fallback.year, month: fallback.month, date: fallback.date };
+        ps.forEach(function (p) { r[p[0]] = p[1]; });
+        return r.year * 10000 + r.month * 100 + r.date;
+    };
+    var kinds = begin.map(function (p) { return p[0]; });
+    var current = {
+        year: kinds.indexOf('year') >= 0 ? now.year : 0,
+        month: kinds.indexOf('month') >= 0 ? now.month : 0,
+        date: kinds.indexOf('date') >= 0 ? now.date : 0
+    };
+    var value = current.year * 10000 + current.month * 100 + current.date;
+    return pacInRange(value, key(begin, current), key(end, current));
+}
+function timeRange() {
+    var now = pacNow(arguments);
+    var a = now.args.map(Number);
+    var begin, end;
+    if (a.length == 1) {
+        begin = a[0] * 3600;
+        end = begin + 3599;
+    } else if (a.length == 2) {
+        begin = a[0] * 3600;
+        end = a[1] * 3600 - 1;
+    } else if (a.length == 4) {
+        begin = a[0] * 3600 + a[1] * 60;
+        end = a[2] * 3600 + a[3] * 60 - 1;
+    } else {
+        begin = a[0] * 3600 + a[1] * 60 + a[2];
+        end = a[3] * 3600 + a[4] * 60 + a[5];
+    }
+    return pacInRange(now.seconds, begin, end);
+}
+"#;
+
+/// The longest time to wait for the proxy of an url, after which the url is connected directly
+const PAC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest time of `dnsResolve`, after which the host is not resolvable
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The maximum number of cached proxies, after which the cache is cleared
+const MAX_CACHED: usize = 1024;
+
+/// Resolve the host to its first IPv4 address
+fn dns_resolve(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let host = match args.first() {
+        Some(host) => host.to_string(context)?.to_std_string_escaped(),
+        None => return Ok(JsValue::null()),
+    };
+    // The system resolver can't be cancelled, so it is left in its thread after the timeout
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let ip = (host.as_str(), 0)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.find(|addr| addr.is_ipv4()))
+            .map(|addr| addr.ip().to_string());
+        let _ = sender.send(ip);
+    });
+    let ip = receiver.recv_timeout(DNS_TIMEOUT).ok().flatten();
+    Ok(match ip {
+        Some(ip) => JsValue::from(js_string!(ip)),
+        None => JsValue::null(),
+    })
+}
+
+/// The IPv4 address of the local host
+fn my_ip_address(_this: &JsValue, _args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
+    // Connecting an udp socket sends nothing, but lets the system choose the local address
+    let ip = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.connect("198.51.100.1:80").map(|_| socket))
+        .and_then(|socket| socket.local_addr())
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".to_owned());
+    Ok(JsValue::from(js_string!(ip)))
+}
+
+fn new_context(script: &str) -> Result<Context> {
+    let mut context = Context::default();
+    context
+        .register_global_callable(js_string!("dnsResolve"), 1, NativeFunction::from_fn_ptr(dns_resolve))
+        .and_then(|_| {
+            context.register_global_callable(js_string!("myIpAddress"), 0, NativeFunction::from_fn_ptr(my_ip_address))
+        })
+        .and_then(|_| context.eval(Source::from_bytes(PAC_UTILS)))
+        .and_then(|_| context.eval(Source::from_bytes(script)))
+        .map_err(|err| Error::PacError(err.to_string()))?;
+    Ok(context)
+}
+
+/// Evaluate `FindProxyForURL` of the script for the requests, until the `Pac` is dropped
+fn evaluate(script: &str, requests: mpsc::Receiver<(Url, mpsc::Sender<Result<String>>)>) {
+    let mut context = match new_context(script) {
+        Ok(context) => context,
+        Err(err) => {
+            // The script is checked by `Pac::new`, so it doesn't happen
+            tracing::error!("PAC: {}", err);
+            return;
+        }
+    };
+    for (url, reply) in requests {
+        let host = url.host_str().unwrap_or("");
+        let args = [JsValue::from(js_string!(url.as_str())), JsValue::from(js_string!(host))];
+        let result = context
+            .global_object()
+            .get(js_string!("FindProxyForURL"), &mut context)
+            .and_then(|func| match func.as_callable() {
+                Some(func) => func.call(&JsValue::undefined(), &args, &mut context),
+                None => Ok(JsValue::undefined()),
+            })
+            .and_then(|value| value.to_string(&mut context))
+            .map(|value| value.to_std_string_escaped())
+            .map_err(|err| Error::PacError(err.to_string()));
+        let _ = reply.send(result);
+    }
+}
+
+/// Proxy auto-config
+///
+/// A PAC file is a javascript file which defines a function `FindProxyForURL(url, host)`. The
+/// function returns the proxies for the url, e.g. "PROXY proxy.example.com:8080; DIRECT".
+/// Only the first one of the proxies is used.
+///
+/// As browsers do, the path and the query of https urls are not given to the script, because
+/// they are encrypted. The script is evaluated in its own thread, because the proxy is found while
+/// a connection is made on the runtime.
+#[derive(Clone)]
+pub struct Pac {
+    script: Arc<str>,
+    // url given to the script -> proxy
+    cache: Arc<Mutex<HashMap<String, Option<Url>>>>,
+    worker: mpsc::Sender<(Url, mpsc::Sender<Result<String>>)>,
+}
+
+impl fmt::Debug for Pac {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pac{{ script: {} bytes }}", self.script.len())
+    }
+}
+
+impl Pac {
+    pub fn new(script: &str) -> Result<Pac> {
+        // Check the script at first
+        new_context(script)?;
+        let script: Arc<str> = Arc::from(script);
+
+        let (worker, requests) = mpsc::channel();
+        {
+            let script = script.clone();
+            thread::Builder::new()
+                .name("pac".to_owned())
+                .spawn(move || evaluate(&script, requests))?;
+        }
+
+        Ok(Pac {
+            script,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            worker,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Pac> {
+        Pac::new(&std::fs::read_to_string(path)?)
+    }
+
+    /// Find the proxy for the url. Return `None` for `DIRECT`.
+    pub fn find_proxy(&self, url: &Url) -> Result<Option<Url>> {
+        let url = script_url(url);
+        let key = url.as_str().to_owned();
+        if let Some(proxy) = self.cache.lock().unwrap().get(&key) {
+            return Ok(proxy.clone());
+        }
+
+        let (reply, result) = mpsc::channel();
+        self.worker
+            .send((url, reply))
+            .map_err(|_| Error::PacError("The evaluation of the script exits".to_owned()))?;
+        let result = result
+            .recv_timeout(PAC_TIMEOUT)
+            .map_err(|_| Error::PacError(format!("No result in {:?}", PAC_TIMEOUT)))??;
+
+        let proxy = parse_pac_result(&result)?;
+        tracing::debug!("PAC: {} -> {:?}", key, proxy.as_ref().map(|u| u.as_str()));
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED {
+            cache.clear();
+        }
+        cache.insert(key, proxy.clone());
+        Ok(proxy)
+    }
+}
+
+/// The url which is given to the script, without the path and the query for https urls
+fn script_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    if url.scheme() == "https" {
+        url.set_path("/");
+        url.set_query(None);
+    }
+    url
+}
+
+/// Parse the first proxy of a result of `FindProxyForURL`
+fn parse_pac_result(result: &str) -> Result<Option<Url>> {
+    let first = result
+        .split(';')
+        .map(str::trim)
+        .find(|s| !s.is_empty())
+        .unwrap_or("DIRECT");
+    let mut parts = first.split_whitespace();
+    let scheme = match parts.next().map(|s| s.to_uppercase()).as_deref() {
+        Some("DIRECT") => return Ok(None),
+        Some("PROXY") | Some("HTTP") => "http",
+        Some("HTTPS") => "https",
+        Some("SOCKS") | Some("SOCKS5") => "socks5",
+        Some("SOCKS4") => "socks4",
+        _ => return Err(Error::PacError(format!("Unknown result: {}", result))),
+    };
+    match parts.next() {
+        Some(addr) => Ok(Some(format!("{}://{}", scheme, addr).parse()?)),
+        None => Err(Error::PacError(format!("No proxy address: {}", result))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pac;
+
+    #[test]
+    fn test_pac() {
+        // Hosts are ip literals when `isInNet` is called, so no name is resolved
+        let pac = Pac::new(
+            r#"
+            function FindProxyForURL(url, host) {
+                if (dnsDomainIs(host, ".example.com")) {
+                    return "SOCKS5 127.0.0.1:1080; DIRECT";
+                }
+                if (isPlainHostName(host) || shExpMatch(host, "*.local") || isInNet(host, "10.0.0.0", "255.0.0.0")) {
+                    return "DIRECT";
+                }
+                return "PROXY proxy.corp:8080; DIRECT";
+            }
+            "#,
+        )
+        .unwrap();
+
+        let proxy = |url: &str| pac.find_proxy(&url.parse().unwrap()).unwrap().map(|u| u.to_string());
+        assert_eq!(proxy("http://intranet/a"), None);
+        assert_eq!(proxy("http://printer.local/a"), None);
+        assert_eq!(proxy("http://10.1.2.3/a"), None);
+        assert_eq!(
+            proxy("https://www.example.com/a"),
+            Some("socks5://127.0.0.1:1080".to_owned())
+        );
+        assert_eq!(proxy("https://192.0.2.1/"), Some("http://proxy.corp:8080/".to_owned()));
+
+        assert!(Pac::new("function FindProxyForURL(url, host) {").is_err());
+    }
+
+    #[test]
+    fn test_pac_url() {
+        let pac = Pac::new(
+            r#"
+            function FindProxyForURL(url, host) {
+                return url.indexOf("/direct?") >= 0 ? "DIRECT" : "PROXY proxy.corp:8080";
+            }
+            "#,
+        )
+        .unwrap();
+
+        // The whole url is given to the script for http, and the proxy is cached for the url
+        let proxy = |url: &str| pac.find_proxy(&url.parse().unwrap()).unwrap().map(|u| u.to_string());
+        assert_eq!(proxy("http://example.com/direct?a=1"), None);
+        assert_eq!(
+            proxy("http://example.com/proxy?a=1"),
+            Some("http://proxy.corp:8080/".to_owned())
+        );
+
+        // The path and the query of https urls are not given to the script
+        assert_eq!(
+            proxy("https://example.com/direct?a=1"),
+            Some("http://proxy.corp:8080/".to_owned())
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+
+use crate::common::net::HttpClient;
+
+struct Route {
+    name: String,
+    client: HttpClient,
+    failed: bool,
+}
+
+/// A pool of `HttpClient`s, each of which connects through a different route, e.g. a server
+/// address.
+///
+/// A route which fails is avoided for the rest of the job.
+#[derive(Clone)]
+pub struct ClientPool {
+    routes: Arc<Mutex<Vec<Route>>>,
+    // Whether the routes are proxies, which answer their failures with statuses
+    proxied: bool,
+}
+
+impl ClientPool {
+    pub fn new(routes: Vec<(String, HttpClient)>) -> ClientPool {
+        assert!(!routes.is_empty(), "`ClientPool::new`: no route");
+        let routes = routes
+            .into_iter()
+            .map(|(name, client)| Route {
+                name,
+                client,
+                failed: false,
+            })
+            .collect();
+        ClientPool {
+            routes: Arc::new(Mutex::new(routes)),
+            proxied: false,
+        }
+    }
+
+    /// A pool whose routes are proxies
+    pub fn proxies(routes: Vec<(String, HttpClient)>) -> ClientPool {
+        ClientPool {
+            proxied: true,
+            ..ClientPool::new(routes)
+        }
+    }
+
+    pub fn single(client: HttpClient) -> ClientPool {
+        ClientPool::new(vec![("default".to_owned(), client)])
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_proxied(&self) -> bool {
+        self.proxied
+    }
+
+    /// The first available route from the `index`, which wraps around the pool
+    pub fn route(&self, index: usize) -> usize {
+        let routes = self.routes.lock().unwrap();
+        let len = routes.len();
+        (0..len)
+            .map(|i| (index + i) % len)
+            .find(|&i| !routes[i].failed)
+            .unwrap_or(index % len)
+    }
+
+    pub fn client(&self, route: usize) -> HttpClient {
+        self.routes.lock().unwrap()[route].client.clone()
+    }
+
+    pub fn name(&self, route: usize) -> String {
+        self.routes.lock().unwrap()[route].name.clone()
+    }
+
+    /// Mark the route failed and return the next available route.
+    /// Return `None` if all routes have failed.
+    pub fn fail(&self, route: usize) -> Option<usize> {
+        let mut routes = self.routes.lock().unwrap();
+        routes[route].failed = true;
+        let len = routes.len();
+        (1..len).map(|i| (route + i) % len).find(|&i| !routes[i].failed)
+    }
+
+    /// Try all routes again if all of them have failed, e.g. before the job is retried
+    pub fn revive(&self) {
+        let mut routes = self.routes.lock().unwrap();
+        if routes.iter().all(|route| route.failed) {
+            routes.iter_mut().for_each(|route| route.failed = false);
+        }
+    }
+}
+
+/// The `ClientPool` of a task, which is kept across the retries of the task, so that the failed
+/// routes are still avoided
+#[derive(Clone, Default)]
+pub struct SharedPool {
+    pool: Arc<Mutex<Option<ClientPool>>>,
+}
+
+impl SharedPool {
+    /// The pool which is built by a previous attempt
+    pub fn get(&self) -> Option<ClientPool> {
+        self.pool.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, pool: ClientPool) {
+        *self.pool.lock().unwrap() = Some(pool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientPool;
+    use crate::common::net::HttpClient;
+
+    fn pool(len: usize) -> ClientPool {
+        ClientPool::new((0..len).map(|i| (i.to_string(), HttpClient::new())).collect())
+    }
+
+    #[test]
+    fn test_rotation() {
+        let pool = pool(3);
+        assert_eq!((0..4).map(|i| pool.route(i)).collect::<Vec<_>>(), vec![0, 1, 2, 0]);
+
+        // Failed routes are skipped
+        assert_eq!(pool.fail(1), Some(2));
+        assert_eq!((0..4).map(|i| pool.route(i)).collect::<Vec<_>>(), vec![0, 2, 2, 0]);
+        assert_eq!(pool.name(pool.route(1)), "2");
+    }
+
+    #[test]
+    fn test_eviction() {
+        let pool = pool(2);
+        assert_eq!(pool.fail(0), Some(1));
+        assert_eq!(pool.fail(1), None);
+
+        // The routes are only revived when all of them have failed
+        pool.revive();
+        assert_eq!(pool.route(0), 0);
+        assert_eq!(pool.fail(0), Some(1));
+        pool.revive();
+        assert_eq!(pool.route(0), 1);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, rc::Rc};
+use std::sync::{Arc, Mutex};
 
 use crate::features::stack::StackLike;
 
@@ -24,28 +24,28 @@ pub type RangeList = Vec<RangePair>;
 
 #[derive(Debug, Clone)]
 pub struct SharedRangList {
-    inner: Rc<RefCell<RangeList>>,
+    inner: Arc<Mutex<RangeList>>,
 }
 
 impl SharedRangList {
     pub fn new(rangelist: RangeList) -> SharedRangList {
         SharedRangList {
-            inner: Rc::new(RefCell::new(rangelist)),
+            inner: Arc::new(Mutex::new(rangelist)),
         }
     }
 }
 
 impl StackLike<RangePair> for SharedRangList {
     fn push(&mut self, pair: RangePair) {
-        self.inner.borrow_mut().push(pair)
+        self.inner.lock().unwrap().push(pair)
     }
 
     fn pop(&mut self) -> Option<RangePair> {
-        self.inner.borrow_mut().pop()
+        self.inner.lock().unwrap().pop()
     }
 
     fn len(&self) -> usize {
-        self.inner.borrow().len()
+        self.inner.lock().unwrap().len()
     }
 }
 
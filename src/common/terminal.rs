@@ -1,3 +1,22 @@
+#[cfg(unix)]
+use std::{
+    io::{stdin, IsTerminal},
+    os::fd::AsRawFd,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use std::io::stdout;
+
+use crossterm::{
+    cursor::Show,
+    execute,
+    terminal::{disable_raw_mode, is_raw_mode_enabled, LeaveAlternateScreen},
+};
+use futures::channel::mpsc::UnboundedSender;
 use term_size::dimensions;
 
 const MIN_TERMINAL_WIDTH: u64 = 60;
@@ -11,3 +30,105 @@ pub fn terminal_width() -> u64 {
         MIN_TERMINAL_WIDTH
     }
 }
+
+/// The settings of the terminal before keys are read
+#[cfg(unix)]
+static SAVED_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// Read keys from the terminal without waiting for the enter key, and send them to the sender
+///
+/// The terminal is restored when the reader is dropped.
+#[cfg(unix)]
+pub struct KeyReader {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl KeyReader {
+    /// Return None if stdin is not a terminal
+    pub fn spawn(sender: UnboundedSender<char>) -> Option<KeyReader> {
+        if !stdin().is_terminal() {
+            return None;
+        }
+
+        let fd = stdin().as_raw_fd();
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+            return None;
+        }
+
+        // Keep the output processing, so printing "\n" still starts a new line
+        let mut raw = termios;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        // Return from reading after 0.1s without input, to check whether the reader is stopped
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 1;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return None;
+        }
+        *SAVED_TERMIOS.lock().unwrap() = Some(termios);
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 1];
+                while !stopped.load(Ordering::SeqCst) {
+                    let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, 1) };
+                    if n < 0 {
+                        // Read again if a signal interrupts the reading, e.g. resizing the terminal
+                        if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                            continue;
+                        }
+                        break;
+                    }
+                    if n == 1 && sender.unbounded_send(buf[0] as char).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+
+        Some(KeyReader {
+            stopped,
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        restore_terminal();
+    }
+}
+
+/// Restore the terminal which is changed for reading keys or the dashboard, e.g. before exiting at once
+pub fn restore_terminal() {
+    #[cfg(unix)]
+    if let Some(termios) = SAVED_TERMIOS.lock().unwrap().take() {
+        unsafe {
+            libc::tcsetattr(stdin().as_raw_fd(), libc::TCSANOW, &termios);
+        }
+    }
+    if is_raw_mode_enabled().unwrap_or(false) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Keys are not supported on this platform
+#[cfg(not(unix))]
+pub struct KeyReader;
+
+#[cfg(not(unix))]
+impl KeyReader {
+    pub fn spawn(_sender: UnboundedSender<char>) -> Option<KeyReader> {
+        None
+    }
+}
@@ -1,8 +1,17 @@
-use std::{fs, io::Read};
+use std::{fs, time::Duration};
 
 use serde::Deserialize;
 
-#[derive(Deserialize, Default)]
+use crate::{
+    arguments::options::DownloadOptions,
+    common::{
+        errors::{Error, Result},
+        liberal::ParseLiteralNumber,
+        net::Url,
+    },
+};
+
+#[derive(Deserialize, Default, Clone)]
 pub struct Config {
     pub(crate) headers: Option<Vec<(String, String)>>,
     pub(crate) concurrency: Option<u64>,
@@ -14,21 +23,49 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn new() -> Config {
+    /// Load the configuration file `~/.config/aget/config`, or the default configuration if it
+    /// doesn't exist
+    pub fn new() -> Result<Config> {
         if let Some(path) = dirs::home_dir() {
             let config_dir = path.join(".config").join("aget");
             if config_dir.is_dir() {
                 let config_path = config_dir.join("config");
                 if config_path.exists() && config_path.is_file() {
-                    let mut fl = fs::File::open(&config_path)
-                        .expect(&format!("Can't open configuration file: {:?}", config_path));
-                    let mut cn = String::new();
-                    fl.read_to_string(&mut cn).unwrap();
-                    let config: Config = toml::from_str(&cn).unwrap();
-                    return config;
+                    let cn = fs::read_to_string(&config_path)
+                        .map_err(|err| Error::InvalidConfig(format!("{}: {}", config_path.display(), err)))?;
+                    let config: Config = toml::from_str(&cn)
+                        .map_err(|err| Error::InvalidConfig(format!("{}: {}", config_path.display(), err)))?;
+                    return Ok(config);
                 }
             }
         }
-        Config::default()
+        Ok(Config::default())
+    }
+
+    /// The download options of the url with the configuration
+    pub fn options(&self, url: Url) -> Result<DownloadOptions> {
+        let mut options = DownloadOptions::new(url);
+        for (k, v) in self.headers.iter().flatten() {
+            options = options.with_header(k, v);
+        }
+        if let Some(concurrency) = self.concurrency {
+            options = options.with_concurrency(concurrency);
+        }
+        if let Some(chunk_size) = self.chunk_size.as_deref() {
+            options = options.with_chunk_size(chunk_size.literal_number()?);
+        }
+        if let Some(timeout) = self.timeout {
+            options = options.with_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = self.dns_timeout {
+            options = options.with_dns_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(retries) = self.retries {
+            options = options.with_retries(retries);
+        }
+        if let Some(retry_wait) = self.retry_wait {
+            options = options.with_retry_wait(retry_wait);
+        }
+        Ok(options)
     }
 }
@@ -1,11 +1,20 @@
-use std::{path::PathBuf, time::Duration};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
-use crate::common::{
-    net::{Method, Url},
-    tasks::TaskType,
+use tracing::Level;
+
+use crate::{
+    app::show::common::ProgressFormat,
+    common::{
+        log::{LogFormat, LogRotation},
+        net::{dns::IpFamily, HttpVersion, Method, Url},
+        tasks::TaskType,
+    },
 };
 
 /// This a arg which gives parameters for apps
+///
+/// Methods which are added after the first ones have default implementations, which are the
+/// defaults of `ag`.
 pub trait Args {
     /// Path of output
     fn output(&self) -> PathBuf;
@@ -25,6 +34,61 @@ pub trait Args {
     /// Proxy: http, https, socks4, socks5
     fn proxy(&self) -> Option<&str>;
 
+    /// Proxy for http urls, which takes precedence over `proxy`
+    fn http_proxy(&self) -> Option<String> {
+        None
+    }
+
+    /// Proxy for https urls, which takes precedence over `proxy`
+    fn https_proxy(&self) -> Option<String> {
+        None
+    }
+
+    /// Comma-separated hosts, domains and ip ranges which are not proxied
+    fn no_proxy(&self) -> Option<String> {
+        None
+    }
+
+    /// `user:password` for proxy authentication
+    fn proxy_user(&self) -> Option<String> {
+        None
+    }
+
+    /// The PAC file which decides the proxy of each url
+    fn proxy_pac(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// All proxies which concurrent requests are rotated across
+    fn proxies(&self) -> Vec<String> {
+        self.proxy().map(|proxy| vec![proxy.to_owned()]).unwrap_or_default()
+    }
+
+    /// Resolve hosts to the given addresses instead of using dns
+    fn resolve(&self) -> Vec<(String, Vec<SocketAddr>)> {
+        vec![]
+    }
+
+    /// The address family used to connect to hosts
+    fn ip_family(&self) -> IpFamily {
+        IpFamily::Any
+    }
+
+    /// Dns servers used to resolve hosts. Use the system resolver if it is empty.
+    fn dns_servers(&self) -> Vec<SocketAddr> {
+        vec![]
+    }
+
+    /// Spread range requests across all addresses of the server
+    fn spread_addrs(&self) -> bool {
+        false
+    }
+
+    /// Connect to the server through this unix domain socket instead of tcp
+    fn unix_socket(&self) -> Option<PathBuf> {
+        None
+    }
+
     /// The maximum time the request is allowed to take.
     fn timeout(&self) -> Duration;
 
@@ -49,24 +113,112 @@ pub trait Args {
     /// Default lifetime period is 75 seconds.
     fn lifetime(&self) -> Duration;
 
-    /// Always return `true`
+    /// Don't follow redirects
     fn disable_redirects(&self) -> bool;
 
+    /// The maximum number of redirects to follow
+    fn max_redirects(&self) -> u64 {
+        10
+    }
+
+    /// Send the headers of credentials to the other origins which are redirected to
+    fn location_trusted(&self) -> bool {
+        false
+    }
+
     /// Skip to verify the server's TLS certificate
     fn skip_verify_tls_cert(&self) -> bool;
 
+    /// The http version used to connect to servers
+    fn http_version(&self) -> HttpVersion {
+        HttpVersion::Http1
+    }
+
+    /// The number of HTTP/2 connections which concurrent requests are multiplexed over
+    fn http2_connections(&self) -> u64 {
+        1
+    }
+
     /// The number of concurrency
+    ///
+    /// For `auto_concurrency`, it is the initial number.
     fn concurrency(&self) -> u64;
 
+    /// The maximum number of connections to a host, which is shared by all tasks in the process
+    fn max_connections_per_host(&self) -> Option<u64> {
+        None
+    }
+
+    /// Tune the number of concurrency by the throughput
+    fn auto_concurrency(&self) -> bool {
+        false
+    }
+
+    /// The maximum number of concurrency for `auto_concurrency`
+    fn max_concurrency(&self) -> u64 {
+        32
+    }
+
+    /// Choose the chunk size by the content length. It is used for `auto_concurrency` if the
+    /// chunk size is not given.
+    fn auto_chunk_size(&self) -> bool {
+        false
+    }
+
+    /// Request a compressed response and decode it. The resource is downloaded in a single stream.
+    fn compressed(&self) -> bool {
+        false
+    }
+
+    /// The maximum number of small ranges which are coalesced into one multi-range request
+    fn max_ranges(&self) -> u64 {
+        16
+    }
+
     /// The chunk size of each concurrency for http task
     fn chunk_size(&self) -> u64;
 
+    /// The speed limit in bytes per second
+    fn limit_rate(&self) -> Option<u64> {
+        None
+    }
+
     /// The number of retry of a task
     fn retries(&self) -> u64;
 
-    /// The internal of each retry
+    /// The wait before the first retry, which is doubled for each retry
     fn retry_wait(&self) -> u64;
 
+    /// The maximum wait between retries
+    fn retry_max_wait(&self) -> u64 {
+        60
+    }
+
+    /// The response status codes which are retried
+    fn retry_statuses(&self) -> Vec<u16> {
+        vec![408, 429, 500, 502, 503, 504]
+    }
+
+    /// The maximum seconds of the whole task, including retries
+    fn max_time(&self) -> Option<u64> {
+        None
+    }
+
+    /// The maximum size of the file in bytes
+    fn max_filesize(&self) -> Option<u64> {
+        None
+    }
+
+    /// The lowest speed in bytes per second, below which the download is aborted after `speed_time`
+    fn speed_limit(&self) -> Option<u64> {
+        None
+    }
+
+    /// The seconds that the speed is below `speed_limit` before the download is aborted
+    fn speed_time(&self) -> u64 {
+        30
+    }
+
     /// Task type
     fn task_type(&self) -> TaskType;
 
@@ -93,4 +245,57 @@ pub trait Args {
 
     /// To quiet mode, if it return true
     fn quiet(&self) -> bool;
+
+    /// The format of the progress output
+    fn progress(&self) -> ProgressFormat {
+        if self.quiet() {
+            ProgressFormat::Quiet
+        } else {
+            ProgressFormat::Bar
+        }
+    }
+
+    /// Show the status of each connection and the map of downloaded blocks
+    fn verbose(&self) -> bool {
+        false
+    }
+
+    /// Write logs to the file instead of stdout
+    fn log_file(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// The maximum level of logs. It is `DEBUG` for the debug mode, and `ERROR` by default.
+    fn log_level(&self) -> Level {
+        if self.debug() {
+            Level::DEBUG
+        } else {
+            Level::ERROR
+        }
+    }
+
+    /// The format of logs
+    fn log_format(&self) -> LogFormat {
+        LogFormat::Text
+    }
+
+    /// How often the log file is rotated
+    fn log_rotation(&self) -> LogRotation {
+        LogRotation::Never
+    }
+
+    /// The maximum number of rotated log files to keep
+    fn log_max_files(&self) -> Option<u64> {
+        None
+    }
+
+    /// Show a dashboard of tasks instead of the progress output
+    fn tui(&self) -> bool {
+        false
+    }
+
+    /// The number of tasks which run at the same time for the dashboard
+    fn jobs(&self) -> u64 {
+        3
+    }
 }
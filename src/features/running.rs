@@ -1,5 +1,16 @@
-use crate::common::errors::Result;
+use std::future::Future;
+
+use crate::{app::status::summary::Summary, common::errors::Result};
 
 pub trait Runnable {
-    fn run(self) -> Result<()>;
+    /// Run the task. Return `None` if the task has been completed before.
+    fn run(self) -> Result<Option<Summary>>;
+}
+
+/// Run the future of a task on a new single-threaded runtime of the current thread
+///
+/// The tasks which are spawned by the future are aborted when it finishes.
+pub(crate) fn block_on<F: Future>(future: F) -> Result<F::Output> {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    Ok(runtime.block_on(future))
 }
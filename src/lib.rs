@@ -3,3 +3,6 @@ pub mod arguments;
 pub mod common;
 pub mod config;
 pub mod features;
+
+pub use app::core::downloader::{DownloadEvent, DownloadTask, Downloader, Progress};
+pub use arguments::options::DownloadOptions;
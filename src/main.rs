@@ -1,87 +1,146 @@
 #![allow(dead_code)]
 
-use std::{process::exit, str::FromStr, thread, time::Duration};
+use std::{process::exit, time::Instant};
 
-use time::{macros::format_description, UtcOffset};
-use tracing_subscriber::fmt::time::OffsetTime;
+use futures::StreamExt;
 
 use aget::{
-    app::core::{bt::BtHandler, http::HttpHandler, m3u8::M3u8Handler},
+    app::{
+        core::{
+            batch::run_batch,
+            signals::{handle_signals, signal_exit_code},
+        },
+        show::{
+            common::ProgressFormat,
+            event::{emit, Event},
+        },
+    },
     arguments::cmd_args::CmdArgs,
-    common::{errors::Error, tasks::TaskType},
-    features::{args::Args, running::Runnable},
+    common::{
+        errors::{exit_code, Error},
+        log::{init_log, local_offset},
+        tasks::TaskType,
+    },
+    features::args::Args,
+    DownloadEvent, Downloader,
 };
 
 fn main() {
-    let cmdargs = CmdArgs::new();
-    let log_level = if cmdargs.debug() { "debug" } else { "error" };
+    // The local offset can only be got before other threads are spawned
+    let local_offset = local_offset();
 
-    let app_name = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string();
+    let cmdargs = CmdArgs::new().unwrap_or_else(|err| fail(err));
 
-    let (non_blocking, _guard) = tracing_appender::non_blocking(std::io::stdout());
-    let local_time = OffsetTime::new(
-        UtcOffset::from_hms(8, 0, 0).unwrap(),
-        format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:2]"),
-    );
+    let app_name = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string();
 
-    let log_level = tracing::Level::from_str(log_level).unwrap();
-    tracing_subscriber::fmt()
-        .with_writer(non_blocking)
-        .with_max_level(log_level)
-        .with_timer(local_time)
-        .init();
+    let _guard = init_log(&cmdargs, local_offset).unwrap_or_else(|err| fail(err));
+    let progress = cmdargs.progress();
+    let json = progress == ProgressFormat::Json;
 
     tracing::debug!("===== Aget-rs {}: begin =====", app_name);
     tracing::debug!("Args: {:?}", cmdargs);
 
-    let tasktype = cmdargs.task_type();
-    for i in 0..cmdargs.retries() + 1 {
-        if i != 0 {
-            println!("Retry {}", i);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap_or_else(|err| fail(err.into()));
+
+    // Run the tasks of all urls with the dashboard
+    if cmdargs.tui() {
+        let downloader = Downloader::with_handle(runtime.handle().clone());
+        match run_batch(
+            &downloader,
+            cmdargs.tasks().iter().map(CmdArgs::options).collect(),
+            cmdargs.jobs(),
+        ) {
+            Ok(true) => return,
+            Ok(false) => exit(signal_exit_code().unwrap_or(exit_code::FAILURE)),
+            Err(err) => fail(err),
         }
+    }
 
-        let result = match tasktype {
-            TaskType::HTTP => {
-                let httphandler = HttpHandler::new(&cmdargs).unwrap();
-                httphandler.run()
-            }
-            TaskType::M3U8 => {
-                let m3u8handler = M3u8Handler::new(&cmdargs).unwrap();
-                m3u8handler.run()
-            }
-            TaskType::BT => {
-                let bthandler = BtHandler::new(&cmdargs);
-                bthandler.run()
-            }
+    let tasktype = cmdargs.task_type();
+    let begin = Instant::now();
+    if json {
+        let task = match tasktype {
+            TaskType::HTTP => "http",
+            TaskType::M3U8 => "m3u8",
+            TaskType::BT => "bt",
         };
+        let _ = emit(&Event::Start {
+            task,
+            url: cmdargs.url().as_str(),
+            output: &cmdargs.output().to_string_lossy(),
+        });
+    }
 
-        if let Err(err) = result {
-            tracing::error!("Error: {:?}", err);
+    let output = cmdargs.output();
+    let result = runtime.block_on(async {
+        let mut task = Downloader::new().download(cmdargs.options());
+        // The task is stopped on signals
+        if let Err(err) = handle_signals(vec![task.control().clone()]) {
+            task.cancel();
+            return Err(err);
+        }
 
-            // if error is "error initializing persistent DHT", remove dht.json
-            if let Error::BitTorrentError(msg) = err {
-                if msg == "error initializing persistent DHT" {
-                    let output_dir = cmdargs.output();
-                    let dht_file = output_dir
-                        .join("..")
-                        .join(output_dir.file_name().unwrap().to_string_lossy().to_string() + ".bt.aget")
-                        .join("dht.json");
-                    if dht_file.exists() {
-                        std::fs::remove_file(dht_file).unwrap();
-                    }
+        while let Some(event) = task.next().await {
+            if let DownloadEvent::Retry {
+                attempt,
+                retries,
+                wait,
+                error,
+            } = event
+            {
+                tracing::error!("Error: {}", error);
+                if json {
+                    let _ = emit(&Event::Retry {
+                        attempt,
+                        retries,
+                        wait: wait.as_secs_f64(),
+                        error,
+                    });
+                } else if progress != ProgressFormat::Quiet {
+                    println!("Retry {} after {:.1}s", attempt, wait.as_secs_f64());
                 }
             }
+        }
+        task.result().await
+    });
 
-            // Retry
-            let retrywait = cmdargs.retry_wait();
-            thread::sleep(Duration::from_secs(retrywait));
-            continue;
-        } else {
-            // Success
-            return;
+    match result {
+        Ok(summary) => {
+            if json {
+                let _ = emit(&Event::Finished {
+                    elapsed: begin.elapsed().as_secs_f64(),
+                    summary,
+                });
+            }
+        }
+        Err(err) => {
+            if json {
+                let _ = emit(&Event::Error { error: err.to_string() });
+            }
+            // The received data is written to the disk, and the same command resumes the stopped task
+            if matches!(err, Error::Cancelled) {
+                tracing::debug!("The task is stopped");
+                if !json && progress != ProgressFormat::Quiet {
+                    println!(
+                        "\nStopped. Run the same command to resume the download of {}",
+                        output.display()
+                    );
+                }
+                exit(signal_exit_code().unwrap_or(err.exit_code()));
+            }
+            tracing::error!("Error: {:?}", err);
+            // The log is written before exiting
+            drop(_guard);
+            exit(err.exit_code());
         }
     }
+}
 
-    // All retries fail
-    exit(1);
+/// Print the error and exit with its code
+fn fail(err: Error) -> ! {
+    eprintln!("Error: {}", err);
+    exit(err.exit_code());
 }